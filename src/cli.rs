@@ -0,0 +1,46 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Argumentos de línea de comandos de EmitHub
+#[derive(Debug, Parser)]
+#[command(name = "emit-hub", version, about = "Real-time broadcasting microservice")]
+pub struct Cli {
+    /// Ruta al archivo de configuración TOML (también vía EMIT_HUB_CONFIG)
+    #[arg(short, long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Restaurar un backup (por nombre de archivo) antes de arrancar el servidor
+    #[arg(long, value_name = "BACKUP_NAME")]
+    pub restore_backup: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Gestionar API keys directamente sobre la base de datos (con el servidor detenido)
+    #[command(subcommand)]
+    Keys(KeysCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum KeysCommand {
+    /// Emitir una nueva API key
+    Issue {
+        /// Nombre descriptivo de la key
+        #[arg(long)]
+        name: String,
+
+        /// Scopes: channels:read, channels:write, broadcast, tokens:issue, admin
+        #[arg(long = "scope", required = true)]
+        scopes: Vec<String>,
+    },
+
+    /// Revocar una API key por id
+    Revoke { id: Uuid },
+
+    /// Listar las API keys
+    List,
+}
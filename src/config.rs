@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::path::{Path, PathBuf};
 
 /// Configuración principal de EmitHub
 /// Puede ser cargada desde variables de entorno o archivo de configuración
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     /// Host donde el servidor escuchará conexiones
     pub host: String,
@@ -31,9 +33,13 @@ pub struct Config {
 
     /// Configuración de persistencia
     pub persistence: PersistenceConfig,
+
+    /// Configuración de autenticación
+    pub auth: AuthConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct CorsConfig {
     /// Orígenes permitidos para CORS (usar "*" para todos)
     pub allowed_origins: Vec<String>,
@@ -49,6 +55,7 @@ pub struct CorsConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct WebSocketConfig {
     /// Timeout para conexiones WebSocket en segundos
     pub connection_timeout: u64,
@@ -61,9 +68,16 @@ pub struct WebSocketConfig {
 
     /// Timeout de pong en segundos
     pub pong_timeout: u64,
+
+    /// Mensajes recientes por canal en memoria para reanudar con `since_seq` (0 = solo redb)
+    pub replay_buffer_size: usize,
+
+    /// Canales a los que puede suscribirse a la vez una conexión del hub (0 = sin límite)
+    pub max_subscriptions_per_connection: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct PersistenceConfig {
     /// Si persistir mensajes por defecto
     pub persist_messages_default: bool,
@@ -74,11 +88,35 @@ pub struct PersistenceConfig {
     /// Días a mantener mensajes antes de limpiarlos
     pub message_retention_days: u32,
 
+    /// Intervalo en minutos entre ejecuciones de la limpieza de mensajes
+    pub retention_interval_minutes: u64,
+
     /// Si hacer backup automático de la base de datos
     pub auto_backup: bool,
 
     /// Intervalo de backup en horas
     pub backup_interval_hours: u32,
+
+    /// Número de backups a conservar (los más antiguos se eliminan)
+    pub backup_keep: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Exigir API key en los endpoints REST y token firmado en las suscripciones.
+    /// Desactivado por defecto: activarlo exige emitir API keys y tokens a los clientes.
+    pub enabled: bool,
+
+    /// Secreto HMAC para firmar tokens de suscriptor; obligatorio con autenticación
+    /// para que los tokens emitidos sobrevivan a un reinicio
+    pub token_secret: Option<String>,
+
+    /// Validez por defecto de los tokens de suscriptor en segundos
+    pub token_ttl_seconds: u64,
+
+    /// Validez máxima que se puede pedir al emitir un token
+    pub max_token_ttl_seconds: u64,
 }
 
 impl Default for Config {
@@ -93,6 +131,7 @@ impl Default for Config {
             cors: CorsConfig::default(),
             websocket: WebSocketConfig::default(),
             persistence: PersistenceConfig::default(),
+            auth: AuthConfig::default(),
         }
     }
 }
@@ -110,6 +149,7 @@ impl Default for CorsConfig {
                 "GET".to_string(),
                 "POST".to_string(),
                 "PUT".to_string(),
+                "PATCH".to_string(),
                 "DELETE".to_string(),
                 "OPTIONS".to_string(),
             ],
@@ -132,6 +172,8 @@ impl Default for WebSocketConfig {
             max_continuation_size: 2_usize.pow(20), // 1MB
             ping_interval: 30,
             pong_timeout: 10,
+            replay_buffer_size: 256,
+            max_subscriptions_per_connection: 100,
         }
     }
 }
@@ -142,51 +184,107 @@ impl Default for PersistenceConfig {
             persist_messages_default: false,
             max_messages_per_channel: 10_000,
             message_retention_days: 30,
+            retention_interval_minutes: 60,
             auto_backup: false,
             backup_interval_hours: 24,
+            backup_keep: 7,
+        }
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            token_secret: None,
+            token_ttl_seconds: 900, // 15 minutos
+            max_token_ttl_seconds: 86_400,
         }
     }
 }
 
 impl Config {
-    /// Cargar configuración desde variables de entorno
-    pub fn from_env() -> anyhow::Result<Self> {
-        let mut config = Config::default();
+    /// Cargar configuración por capas: valores por defecto, archivo TOML y variables de entorno.
+    /// El archivo se toma de `path` o, si no se indica, de `EMIT_HUB_CONFIG`.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(|| env::var("EMIT_HUB_CONFIG").ok().map(PathBuf::from));
+
+        let mut config = match &path {
+            Some(path) => Self::from_file(path)?,
+            None => Config::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Cargar configuración desde un archivo TOML. Las secciones parciales
+    /// se completan con los valores por defecto.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Cannot read config file {:?}: {}", path, e)
+        })?;
+
+        Self::from_toml_str(&contents).map_err(|e| {
+            anyhow::anyhow!("Invalid config file {:?}: {}", path, e)
+        })
+    }
 
+    fn from_toml_str(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Configuración efectiva en formato TOML, para el log de arranque.
+    /// Los secretos se enmascaran.
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        let mut redacted = self.clone();
+        if redacted.auth.token_secret.is_some() {
+            redacted.auth.token_secret = Some("********".to_string());
+        }
+
+        Ok(toml::to_string_pretty(&redacted)?)
+    }
+
+    /// Sobrescribir valores con las variables de entorno definidas
+    fn apply_env(&mut self) -> anyhow::Result<()> {
         // Configuración básica del servidor
         if let Ok(host) = env::var("EMIT_HUB_HOST") {
-            config.host = host;
+            self.host = host;
         }
 
         if let Ok(port) = env::var("EMIT_HUB_PORT") {
-            config.port = port.parse().map_err(|e| {
+            self.port = port.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid port number '{}': {}", port, e)
             })?;
         }
 
         if let Ok(db_path) = env::var("EMIT_HUB_DB_PATH") {
-            config.db_path = db_path;
+            self.db_path = db_path;
         }
 
         if let Ok(max_conn) = env::var("EMIT_HUB_MAX_CONNECTIONS") {
-            config.max_connections_per_channel = max_conn.parse().map_err(|e| {
+            self.max_connections_per_channel = max_conn.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid max connections '{}': {}", max_conn, e)
             })?;
         }
 
         if let Ok(msg_size) = env::var("EMIT_HUB_MESSAGE_SIZE_LIMIT") {
-            config.message_size_limit = msg_size.parse().map_err(|e| {
+            self.message_size_limit = msg_size.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid message size limit '{}': {}", msg_size, e)
             })?;
         }
 
         if let Ok(log_level) = env::var("EMIT_HUB_LOG_LEVEL") {
-            config.log_level = log_level;
+            self.log_level = log_level;
         }
 
         // Configuración CORS
         if let Ok(origins) = env::var("EMIT_HUB_CORS_ORIGINS") {
-            config.cors.allowed_origins = origins
+            self.cors.allowed_origins = origins
                 .split(',')
                 .map(|s| s.trim().to_string())
                 .collect();
@@ -194,40 +292,102 @@ impl Config {
 
         // Configuración WebSocket
         if let Ok(timeout) = env::var("EMIT_HUB_WS_TIMEOUT") {
-            config.websocket.connection_timeout = timeout.parse().map_err(|e| {
+            self.websocket.connection_timeout = timeout.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid WebSocket timeout '{}': {}", timeout, e)
             })?;
         }
 
         if let Ok(ping_interval) = env::var("EMIT_HUB_WS_PING_INTERVAL") {
-            config.websocket.ping_interval = ping_interval.parse().map_err(|e| {
+            self.websocket.ping_interval = ping_interval.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid ping interval '{}': {}", ping_interval, e)
             })?;
         }
 
+        if let Ok(max_size) = env::var("EMIT_HUB_WS_MAX_CONTINUATION_SIZE") {
+            self.websocket.max_continuation_size = max_size.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid max continuation size '{}': {}", max_size, e)
+            })?;
+        }
+
+        if let Ok(pong_timeout) = env::var("EMIT_HUB_WS_PONG_TIMEOUT") {
+            self.websocket.pong_timeout = pong_timeout.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid pong timeout '{}': {}", pong_timeout, e)
+            })?;
+        }
+
+        if let Ok(replay_buffer) = env::var("EMIT_HUB_WS_REPLAY_BUFFER") {
+            self.websocket.replay_buffer_size = replay_buffer.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid replay buffer size '{}': {}", replay_buffer, e)
+            })?;
+        }
+
+        if let Ok(max_subscriptions) = env::var("EMIT_HUB_WS_MAX_SUBSCRIPTIONS") {
+            self.websocket.max_subscriptions_per_connection = max_subscriptions.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid max subscriptions per connection '{}': {}", max_subscriptions, e)
+            })?;
+        }
+
         // Configuración de persistencia
         if let Ok(persist) = env::var("EMIT_HUB_PERSIST_MESSAGES") {
-            config.persistence.persist_messages_default = persist.parse().map_err(|e| {
+            self.persistence.persist_messages_default = persist.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid persist messages value '{}': {}", persist, e)
             })?;
         }
 
         if let Ok(retention) = env::var("EMIT_HUB_MESSAGE_RETENTION_DAYS") {
-            config.persistence.message_retention_days = retention.parse().map_err(|e| {
+            self.persistence.message_retention_days = retention.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid retention days '{}': {}", retention, e)
             })?;
         }
 
+        if let Ok(max_messages) = env::var("EMIT_HUB_MAX_MESSAGES_PER_CHANNEL") {
+            self.persistence.max_messages_per_channel = max_messages.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid max messages per channel '{}': {}", max_messages, e)
+            })?;
+        }
+
+        if let Ok(interval) = env::var("EMIT_HUB_RETENTION_INTERVAL_MINUTES") {
+            self.persistence.retention_interval_minutes = interval.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid retention interval '{}': {}", interval, e)
+            })?;
+        }
+
         if let Ok(auto_backup) = env::var("EMIT_HUB_AUTO_BACKUP") {
-            config.persistence.auto_backup = auto_backup.parse().map_err(|e| {
+            self.persistence.auto_backup = auto_backup.parse().map_err(|e| {
                 anyhow::anyhow!("Invalid auto backup value '{}': {}", auto_backup, e)
             })?;
         }
 
-        // Validar configuración
-        config.validate()?;
+        if let Ok(interval) = env::var("EMIT_HUB_BACKUP_INTERVAL_HOURS") {
+            self.persistence.backup_interval_hours = interval.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid backup interval '{}': {}", interval, e)
+            })?;
+        }
 
-        Ok(config)
+        if let Ok(keep) = env::var("EMIT_HUB_BACKUP_KEEP") {
+            self.persistence.backup_keep = keep.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid backup keep count '{}': {}", keep, e)
+            })?;
+        }
+
+        // Configuración de autenticación
+        if let Ok(enabled) = env::var("EMIT_HUB_AUTH_ENABLED") {
+            self.auth.enabled = enabled.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid auth enabled value '{}': {}", enabled, e)
+            })?;
+        }
+
+        if let Ok(secret) = env::var("EMIT_HUB_TOKEN_SECRET") {
+            self.auth.token_secret = Some(secret);
+        }
+
+        if let Ok(ttl) = env::var("EMIT_HUB_TOKEN_TTL") {
+            self.auth.token_ttl_seconds = ttl.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid token TTL '{}': {}", ttl, e)
+            })?;
+        }
+
+        Ok(())
     }
 
     /// Validar que la configuración sea correcta
@@ -248,17 +408,53 @@ impl Config {
             return Err(anyhow::anyhow!("WebSocket connection timeout must be greater than 0"));
         }
 
+        if self.auth.token_ttl_seconds == 0 || self.auth.token_ttl_seconds > self.auth.max_token_ttl_seconds {
+            return Err(anyhow::anyhow!("Token TTL must be between 1 and max_token_ttl_seconds"));
+        }
+
+        if self.auth.enabled && self.auth.token_secret.is_none() {
+            return Err(anyhow::anyhow!("auth.token_secret is required when authentication is enabled"));
+        }
+
+        if self.auth.token_secret.as_ref().is_some_and(|secret| secret.len() < 32) {
+            return Err(anyhow::anyhow!("Token secret must be at least 32 characters"));
+        }
+
+        if self.websocket.max_continuation_size == 0 {
+            return Err(anyhow::anyhow!("WebSocket max continuation size must be greater than 0"));
+        }
+
+        if self.websocket.ping_interval > 0 && self.websocket.pong_timeout == 0 {
+            return Err(anyhow::anyhow!("WebSocket pong timeout must be greater than 0 when ping is enabled"));
+        }
+
         if self.persistence.message_retention_days == 0 {
             return Err(anyhow::anyhow!("Message retention days must be greater than 0"));
         }
 
+        if self.persistence.max_messages_per_channel == 0 {
+            return Err(anyhow::anyhow!("Max messages per channel must be greater than 0"));
+        }
+
+        if self.persistence.retention_interval_minutes == 0 {
+            return Err(anyhow::anyhow!("Retention interval must be greater than 0"));
+        }
+
+        if self.persistence.backup_interval_hours == 0 {
+            return Err(anyhow::anyhow!("Backup interval must be greater than 0"));
+        }
+
+        if self.persistence.backup_keep == 0 {
+            return Err(anyhow::anyhow!("Backup keep count must be greater than 0"));
+        }
+
         // Validar que el directorio de la base de datos exista o se pueda crear
-        if let Some(parent) = std::path::Path::new(&self.db_path).parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent).map_err(|e| {
-                    anyhow::anyhow!("Cannot create database directory {:?}: {}", parent, e)
-                })?;
-            }
+        if let Some(parent) = std::path::Path::new(&self.db_path).parent()
+            && !parent.exists()
+        {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                anyhow::anyhow!("Cannot create database directory {:?}: {}", parent, e)
+            })?;
         }
 
         Ok(())
@@ -296,6 +492,29 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_heartbeat_config_validation() {
+        let mut config = Config::default();
+
+        // Sin ping el pong_timeout no se usa y puede ser 0
+        config.websocket.ping_interval = 0;
+        config.websocket.pong_timeout = 0;
+        assert!(config.validate().is_ok());
+
+        config.websocket.ping_interval = 30;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_auth_requires_token_secret() {
+        let mut config = Config::default();
+        config.auth.enabled = true;
+        assert!(config.validate().is_err());
+
+        config.auth.token_secret = Some("x".repeat(32));
+        assert!(config.validate().is_ok());
+    }
+
     #[test]
     fn test_env_config() {
         struct EnvGuard;
@@ -316,10 +535,39 @@ mod tests {
             env::set_var("EMIT_HUB_MAX_CONNECTIONS", "2000");
         }
 
-        let config = Config::from_env().unwrap();
+        let config = Config::load(None).unwrap();
 
         assert_eq!(config.host, "0.0.0.0");
         assert_eq!(config.port, 9000);
         assert_eq!(config.max_connections_per_channel, 2000);
     }
+
+    #[test]
+    fn test_partial_toml_config() {
+        let config = Config::from_toml_str(
+            r#"
+            port = 3000
+
+            [websocket]
+            ping_interval = 45
+
+            [persistence]
+            auto_backup = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.port, 3000);
+        assert_eq!(config.host, "127.0.0.1");
+        assert_eq!(config.websocket.ping_interval, 45);
+        assert_eq!(config.websocket.pong_timeout, 10);
+        assert!(config.persistence.auto_backup);
+        assert_eq!(config.persistence.max_messages_per_channel, 10_000);
+    }
+
+    #[test]
+    fn test_unknown_toml_key() {
+        let err = Config::from_toml_str("[cors]\nallowed_origin = [\"*\"]\n").unwrap_err();
+        assert!(err.to_string().contains("allowed_origin"));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,87 @@
+use crate::models::auth::Scope;
+use crate::models::channel::ChannelStatus;
+use actix_web::error::JsonPayloadError;
+use actix_web::http::header;
+use actix_web::{HttpRequest, HttpResponse};
+use std::fmt;
+use std::time::Duration;
+
+/// Errores de los servicios que se traducen a un código HTTP concreto.
+/// El resto de errores (`anyhow`) se siguen respondiendo como 400.
+#[derive(Debug)]
+pub enum AppError {
+    ChannelNotFound,
+    RateLimited { retry_after: Duration },
+    PayloadTooLarge { size: usize, limit: usize },
+    Unauthorized,
+    Forbidden { scope: Scope },
+    InvalidTransition { from: ChannelStatus, to: ChannelStatus },
+    PauseBufferFull { max_messages: usize, max_bytes: usize },
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::ChannelNotFound => write!(f, "Channel not found"),
+            AppError::RateLimited { retry_after } => {
+                write!(f, "Rate limit exceeded, retry after {}s", retry_after_secs(retry_after))
+            }
+            AppError::PayloadTooLarge { size, limit } => {
+                write!(f, "Message size {} bytes exceeds limit of {} bytes", size, limit)
+            }
+            AppError::Unauthorized => write!(f, "Missing or invalid API key"),
+            AppError::Forbidden { scope } => write!(f, "API key lacks required scope '{}'", scope),
+            AppError::InvalidTransition { from, to } => {
+                write!(f, "Cannot transition channel from {:?} to {:?}", from, to)
+            }
+            AppError::PauseBufferFull { max_messages, max_bytes } => write!(
+                f,
+                "Channel is paused and its buffer is full ({} messages / {} bytes)",
+                max_messages, max_bytes
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+fn retry_after_secs(retry_after: &Duration) -> u64 {
+    retry_after.as_secs_f64().ceil().max(1.0) as u64
+}
+
+/// Convierte un error de servicio en la respuesta HTTP correspondiente
+pub fn error_response(e: &anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<AppError>() {
+        Some(AppError::ChannelNotFound) => HttpResponse::NotFound().json("Channel not found"),
+        Some(AppError::RateLimited { retry_after }) => HttpResponse::TooManyRequests()
+            .insert_header((header::RETRY_AFTER, retry_after_secs(retry_after).to_string()))
+            .json(format!("Error: {}", e)),
+        Some(AppError::PayloadTooLarge { .. }) => {
+            HttpResponse::PayloadTooLarge().json(format!("Error: {}", e))
+        }
+        Some(AppError::Unauthorized) => HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .json(format!("Error: {}", e)),
+        Some(AppError::Forbidden { .. }) => HttpResponse::Forbidden().json(format!("Error: {}", e)),
+        Some(AppError::InvalidTransition { .. }) => HttpResponse::Conflict().json(format!("Error: {}", e)),
+        Some(AppError::PauseBufferFull { .. }) => {
+            HttpResponse::ServiceUnavailable().json(format!("Error: {}", e))
+        }
+        None => HttpResponse::BadRequest().json(format!("Error: {}", e)),
+    }
+}
+
+/// Respuesta para cuerpos JSON rechazados por `web::JsonConfig`
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let response = match &err {
+        JsonPayloadError::OverflowKnownLength { length, limit } => error_response(
+            &AppError::PayloadTooLarge { size: *length, limit: *limit }.into(),
+        ),
+        JsonPayloadError::Overflow { limit } => {
+            HttpResponse::PayloadTooLarge().json(format!("Error: Request body exceeds limit of {} bytes", limit))
+        }
+        _ => HttpResponse::BadRequest().json(format!("Error: {}", err)),
+    };
+
+    actix_web::error::InternalError::from_response(err, response).into()
+}
@@ -0,0 +1,54 @@
+use crate::handler::auth::require_scope;
+use crate::models::auth::Scope;
+use crate::services::backup_service::BackupService;
+use crate::services::retention_service::RetentionService;
+use crate::state::AppState;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Result};
+
+#[get("/admin/retention")]
+async fn get_retention_report(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    match state.last_retention_report.read().await.as_ref() {
+        Some(report) => Ok(HttpResponse::Ok().json(report)),
+        None => Ok(HttpResponse::NotFound().json("Retention has not run yet")),
+    }
+}
+
+#[post("/admin/retention/run")]
+async fn run_retention(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    match RetentionService::run(state.get_ref()).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(format!("Error: {}", e))),
+    }
+}
+
+#[post("/admin/backups")]
+async fn create_backup(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    match BackupService::create_backup(state.get_ref()).await {
+        Ok(backup) => Ok(HttpResponse::Created().json(backup)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(format!("Error: {}", e))),
+    }
+}
+
+#[get("/admin/backups")]
+async fn list_backups(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    match BackupService::list_backups(&state.config.db_path) {
+        Ok(backups) => Ok(HttpResponse::Ok().json(backups)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(format!("Error: {}", e))),
+    }
+}
@@ -0,0 +1,171 @@
+use crate::error::error_response;
+use crate::models::auth::{ApiKey, CreateApiKeyRequest, Scope};
+use crate::services::auth_service::AuthService;
+use crate::state::AppState;
+use actix_web::http::header;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Result};
+use uuid::Uuid;
+
+const API_KEY_HEADER: &str = "X-API-Key";
+/// Parámetro de query con el token de suscriptor (`/ws`, `/events`, `/poll`)
+const TOKEN_QUERY_PARAM: &str = "token";
+
+/// Línea de petición para el access log con el token de suscriptor enmascarado
+pub fn redacted_request_line(method: &str, path: &str, query: &str) -> String {
+    if query.is_empty() {
+        return format!("{} {}", method, path);
+    }
+
+    let query = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((TOKEN_QUERY_PARAM, _)) => format!("{}=********", TOKEN_QUERY_PARAM),
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+
+    format!("{} {}?{}", method, path, query)
+}
+
+/// Valor de `Authorization: Bearer <token>`
+pub fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Extrae el secreto de `Authorization: Bearer <key>` o de `X-API-Key`
+fn api_key_from_request(req: &HttpRequest) -> Option<&str> {
+    bearer_token(req).or_else(|| {
+        req.headers()
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+    })
+}
+
+/// Comprueba que la petición tenga una API key con `scope`.
+/// Con `auth.enabled = false` todas las peticiones pasan y no hay key asociada.
+pub async fn require_scope(
+    req: &HttpRequest,
+    state: &AppState,
+    scope: Scope,
+) -> std::result::Result<Option<ApiKey>, HttpResponse> {
+    if !state.config.auth.enabled {
+        return Ok(None);
+    }
+
+    AuthService::authorize(state, api_key_from_request(req), scope)
+        .await
+        .map(Some)
+        .map_err(|e| error_response(&e))
+}
+
+/// Actor que se registra en auditoría: el id de la API key, o `anonymous` sin autenticación.
+/// El nombre no sirve porque puede repetirse entre keys.
+pub fn actor_id(api_key: Option<&ApiKey>) -> String {
+    api_key.map_or_else(|| "anonymous".to_string(), |api_key| api_key.id.to_string())
+}
+
+/// Sin autenticación cualquiera podría emitir keys que pasarían a ser credenciales válidas al
+/// activarla, así que entonces solo se emiten desde la CLI (`emit-hub keys issue`)
+#[post("/admin/keys")]
+async fn create_api_key(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    request: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse> {
+    if !state.config.auth.enabled {
+        return Ok(HttpResponse::Forbidden()
+            .json("Error: API keys cannot be issued over REST while authentication is disabled; use 'emit-hub keys issue'"));
+    }
+
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    let request = request.into_inner();
+    match AuthService::issue_key(state.get_ref(), request.name, request.scopes).await {
+        Ok(issued) => Ok(HttpResponse::Created().json(issued)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+#[get("/admin/keys")]
+async fn list_api_keys(req: HttpRequest, state: web::Data<AppState>) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    match AuthService::list_keys(state.get_ref()).await {
+        Ok(api_keys) => Ok(HttpResponse::Ok().json(api_keys)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(format!("Error: {}", e))),
+    }
+}
+
+#[delete("/admin/keys/{key_id}")]
+async fn revoke_api_key(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Admin).await {
+        return Ok(response);
+    }
+
+    match AuthService::revoke_key(state.get_ref(), path.into_inner()).await {
+        Ok(api_key) => Ok(HttpResponse::Ok().json(api_key)),
+        Err(e) => Ok(HttpResponse::NotFound().json(format!("Error: {}", e))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support::{test_state, TempDir};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+
+    #[test]
+    fn test_request_line_redacts_token() {
+        assert_eq!(
+            redacted_request_line("GET", "/api/v1/channels/x/ws", "since_seq=4&token=abc.def&format=v1"),
+            "GET /api/v1/channels/x/ws?since_seq=4&token=********&format=v1"
+        );
+        assert_eq!(redacted_request_line("GET", "/health", ""), "GET /health");
+    }
+
+    #[test]
+    fn test_actor_is_the_key_id() {
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            name: "deploy".to_string(),
+            prefix: "ehk_1234".to_string(),
+            scopes: vec![Scope::ChannelsWrite],
+            created_at: chrono::Utc::now(),
+            revoked_at: None,
+        };
+
+        assert_eq!(actor_id(Some(&api_key)), api_key.id.to_string());
+        assert_eq!(actor_id(None), "anonymous");
+    }
+
+    #[actix_web::test]
+    async fn test_no_key_issuance_without_auth() {
+        let dir = TempDir::new();
+        let state = web::Data::new(test_state(&dir).await);
+        let app = init_service(App::new().app_data(state.clone()).service(create_api_key)).await;
+
+        let req = TestRequest::post()
+            .uri("/admin/keys")
+            .set_json(serde_json::json!({ "name": "intruder", "scopes": ["admin"] }))
+            .to_request();
+
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+        assert!(state.list_api_keys().await.unwrap().is_empty());
+    }
+}
@@ -1,18 +1,32 @@
-use crate::models::channel::{CreateChannelRequest};
-use crate::models::message::BroadcastRequest;
+use crate::error::error_response;
+use crate::handler::auth::{actor_id, require_scope};
+use crate::models::auth::{CreateSubscriberTokenRequest, Scope};
+use crate::models::channel::{
+    CreateChannelRequest, DeleteChannelQuery, DeleteMode, ListChannelsQuery, TransitionQuery,
+    UpdateChannelRequest,
+};
+use crate::models::message::{BroadcastRequest, MessageHistoryQuery};
 
 use crate::state::AppState;
-use actix_web::{get, post, put, web, HttpResponse, Result};
+use actix_web::guard::GuardContext;
+use actix_web::http::header::{self, HeaderMap};
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse, Result};
 
 use uuid::Uuid;
 use crate::services::channel_service::ChannelService;
 use crate::services::message_service::MessageService;
+use crate::services::token_service::TokenService;
 
 #[post("/channels")]
 async fn create_channel(
+    req: HttpRequest,
     state: web::Data<AppState>,
     request: web::Json<CreateChannelRequest>,
 ) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsWrite).await {
+        return Ok(response);
+    }
+
     match ChannelService::create_channel(state.get_ref(), request.into_inner()).await {
         Ok(channel) => Ok(HttpResponse::Created().json(channel)),
         Err(e) => Ok(HttpResponse::BadRequest().json(format!("Error: {}", e))),
@@ -21,72 +35,137 @@ async fn create_channel(
 
 
 #[get("/channels")]
-async fn list_channels(state: web::Data<AppState>) -> Result<HttpResponse> {
-    let channels = ChannelService::list_channels(state.get_ref()).await;
-    Ok(HttpResponse::Ok().json(channels))
+async fn list_channels(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<ListChannelsQuery>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsRead).await {
+        return Ok(response);
+    }
+
+    match ChannelService::list_channels(state.get_ref(), query.into_inner().status).await {
+        Ok(channels) => Ok(HttpResponse::Ok().json(channels)),
+        Err(e) => Ok(error_response(&e)),
+    }
 }
 
 #[get("/channels/{channel_id}")]
 async fn get_channel(
+    req: HttpRequest,
     state: web::Data<AppState>,
     path: web::Path<Uuid>,
 ) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsRead).await {
+        return Ok(response);
+    }
+
     let channel_id = path.into_inner();
 
-    match state.get_channel(&channel_id).await {
-        Some(channel) => Ok(HttpResponse::Ok().json(channel)),
-        None => Ok(HttpResponse::NotFound().json("Channel not found")),
+    match ChannelService::get_channel(state.get_ref(), channel_id).await {
+        Ok(channel) => Ok(HttpResponse::Ok().json(channel)),
+        Err(e) => Ok(error_response(&e)),
     }
 }
 
 
 #[put("/channels/{channel_id}/start")]
 async fn start_channel(
+    req: HttpRequest,
     state: web::Data<AppState>,
     path: web::Path<Uuid>,
+    query: web::Query<TransitionQuery>,
 ) -> Result<HttpResponse> {
+    let api_key = match require_scope(&req, &state, Scope::ChannelsWrite).await {
+        Ok(api_key) => api_key,
+        Err(response) => return Ok(response),
+    };
+
     let channel_id = path.into_inner();
+    let actor = actor_id(api_key.as_ref());
 
-    match ChannelService::start_channel(state.get_ref(), channel_id).await {
+    match ChannelService::start_channel(state.get_ref(), channel_id, &actor, query.into_inner().reason).await {
         Ok(channel) => Ok(HttpResponse::Ok().json(channel)),
-        Err(e) => Ok(HttpResponse::BadRequest().json(format!("Error: {}", e))),
+        Err(e) => Ok(error_response(&e)),
     }
 }
 
 #[put("/channels/{channel_id}/pause")]
 async fn pause_channel(
+    req: HttpRequest,
     state: web::Data<AppState>,
     path: web::Path<Uuid>,
+    query: web::Query<TransitionQuery>,
 ) -> Result<HttpResponse> {
+    let api_key = match require_scope(&req, &state, Scope::ChannelsWrite).await {
+        Ok(api_key) => api_key,
+        Err(response) => return Ok(response),
+    };
+
     let channel_id = path.into_inner();
+    let actor = actor_id(api_key.as_ref());
 
-    match ChannelService::pause_channel(state.get_ref(), channel_id).await {
+    match ChannelService::pause_channel(state.get_ref(), channel_id, &actor, query.into_inner().reason).await {
         Ok(channel) => Ok(HttpResponse::Ok().json(channel)),
-        Err(e) => Ok(HttpResponse::BadRequest().json(format!("Error: {}", e))),
+        Err(e) => Ok(error_response(&e)),
     }
 }
 
 
 #[put("/channels/{channel_id}/stop")]
 async fn stop_channel(
+    req: HttpRequest,
     state: web::Data<AppState>,
     path: web::Path<Uuid>,
+    query: web::Query<TransitionQuery>,
 ) -> Result<HttpResponse> {
+    let api_key = match require_scope(&req, &state, Scope::ChannelsWrite).await {
+        Ok(api_key) => api_key,
+        Err(response) => return Ok(response),
+    };
+
     let channel_id = path.into_inner();
+    let actor = actor_id(api_key.as_ref());
 
-    match ChannelService::stop_channel(state.get_ref().into(), channel_id).await {
+    match ChannelService::stop_channel(state.get_ref(), channel_id, &actor, query.into_inner().reason).await {
         Ok(channel) => Ok(HttpResponse::Ok().json(channel)),
-        Err(e) => Ok(HttpResponse::BadRequest().json(format!("Error: {}", e))),
+        Err(e) => Ok(error_response(&e)),
     }
 }
 
 
+#[post("/channels/{channel_id}/restore")]
+async fn restore_channel(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<TransitionQuery>,
+) -> Result<HttpResponse> {
+    let api_key = match require_scope(&req, &state, Scope::ChannelsWrite).await {
+        Ok(api_key) => api_key,
+        Err(response) => return Ok(response),
+    };
+
+    let channel_id = path.into_inner();
+    let actor = actor_id(api_key.as_ref());
+
+    match ChannelService::restore_channel(state.get_ref(), channel_id, &actor, query.into_inner().reason).await {
+        Ok(channel) => Ok(HttpResponse::Ok().json(channel)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
 #[post("/channels/{channel_id}/broadcast")]
 async fn broadcast_message(
+    req: HttpRequest,
     state: web::Data<AppState>,
     path: web::Path<Uuid>,
     request: web::Json<BroadcastRequest>,
 ) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Broadcast).await {
+        return Ok(response);
+    }
+
     let channel_id = path.into_inner();
 
     match MessageService::broadcast_message(
@@ -94,11 +173,226 @@ async fn broadcast_message(
         channel_id,
         request.into_inner(),
     ).await {
-        Ok((message, sent_count)) => Ok(HttpResponse::Ok().json(serde_json::json!({
+        Ok((message, sent_count, delivery)) => Ok(HttpResponse::Ok().json(serde_json::json!({
             "message": message,
             "sent_to": sent_count,
+            "delivery": delivery,
             "status": "success"
         }))),
-        Err(e) => Ok(HttpResponse::BadRequest().json(format!("Error: {}", e))),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+/// Publica el cuerpo en bruto como mensaje binario con el `Content-Type` de la petición.
+/// Los cuerpos JSON siguen llegando a `broadcast_message`.
+#[post("/channels/{channel_id}/broadcast", guard = "binary_body")]
+async fn broadcast_binary(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    body: web::Bytes,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::Broadcast).await {
+        return Ok(response);
+    }
+
+    let channel_id = path.into_inner();
+    let content_type = request_content_type(req.headers()).unwrap_or_default();
+
+    match MessageService::broadcast_binary(state.get_ref(), channel_id, content_type, body.to_vec()).await {
+        Ok((message, sent_count, delivery)) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "message": message.without_payload(),
+            "size": message.size(),
+            "sent_to": sent_count,
+            "delivery": delivery,
+            "status": "success"
+        }))),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+fn request_content_type(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok())
+}
+
+/// Cualquier cuerpo con `Content-Type` que no sea JSON se publica como binario
+fn binary_body(ctx: &GuardContext) -> bool {
+    request_content_type(ctx.head().headers()).is_some_and(|content_type| {
+        let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        essence != "application/json" && !essence.ends_with("+json")
+    })
+}
+
+#[get("/channels/{channel_id}/messages")]
+async fn get_channel_messages(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<MessageHistoryQuery>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsRead).await {
+        return Ok(response);
+    }
+
+    // El servicio también encuentra los canales que solo están en redb, como los detenidos
+    match MessageService::get_history(state.get_ref(), path.into_inner(), query.into_inner()).await {
+        Ok(page) => Ok(HttpResponse::Ok().json(page)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+#[get("/channels/{channel_id}/connections")]
+async fn list_channel_connections(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsRead).await {
+        return Ok(response);
+    }
+
+    let channel_id = path.into_inner();
+
+    if state.get_channel(&channel_id).await.is_none() {
+        return Ok(HttpResponse::NotFound().json("Channel not found"));
+    }
+
+    Ok(HttpResponse::Ok().json(state.list_connections(&channel_id).await))
+}
+
+/// Conexión por id con una entrada por canal suscrito; las del hub pueden tener varias o ninguna
+#[get("/connections/{connection_id}")]
+async fn get_connection(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsRead).await {
+        return Ok(response);
+    }
+
+    match state.find_connection(&path.into_inner()).await {
+        Some(connection) => Ok(HttpResponse::Ok().json(connection)),
+        None => Ok(HttpResponse::NotFound().json("Connection not found")),
+    }
+}
+
+#[post("/channels/{channel_id}/tokens")]
+async fn create_subscriber_token(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    request: web::Json<CreateSubscriberTokenRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::TokensIssue).await {
+        return Ok(response);
     }
-}
\ No newline at end of file
+
+    let channel_id = path.into_inner();
+
+    if state.get_channel(&channel_id).await.is_none() {
+        return Ok(HttpResponse::NotFound().json("Channel not found"));
+    }
+
+    match TokenService::issue(state.get_ref(), channel_id, request.into_inner()).await {
+        Ok(token) => Ok(HttpResponse::Created().json(token)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+#[delete("/channels/{channel_id}")]
+async fn delete_channel(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<DeleteChannelQuery>,
+) -> Result<HttpResponse> {
+    let api_key = match require_scope(&req, &state, Scope::ChannelsWrite).await {
+        Ok(api_key) => api_key,
+        Err(response) => return Ok(response),
+    };
+
+    let channel_id = path.into_inner();
+    let query = query.into_inner();
+
+    let mode = query.mode.unwrap_or_default();
+
+    match ChannelService::delete_channel(
+        state.get_ref(),
+        channel_id,
+        mode,
+        query.purge_messages.unwrap_or(mode == DeleteMode::Hard),
+        &actor_id(api_key.as_ref()),
+        query.reason,
+    ).await {
+        Ok(response) => Ok(HttpResponse::Ok().json(response)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+#[patch("/channels/{channel_id}")]
+async fn update_channel(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    request: web::Json<UpdateChannelRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsWrite).await {
+        return Ok(response);
+    }
+
+    let channel_id = path.into_inner();
+
+    match ChannelService::update_channel(state.get_ref(), channel_id, request.into_inner()).await {
+        Ok(channel) => Ok(HttpResponse::Ok().json(channel)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+#[get("/channels/{channel_id}/history")]
+async fn get_channel_history(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_scope(&req, &state, Scope::ChannelsRead).await {
+        return Ok(response);
+    }
+
+    let channel_id = path.into_inner();
+
+    match ChannelService::channel_history(state.get_ref(), channel_id).await {
+        Ok(history) => Ok(HttpResponse::Ok().json(history)),
+        Err(e) => Ok(error_response(&e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::channel::{ChannelSettings, ChannelStatus};
+    use crate::utils::test_support::{test_channel, test_state, text_message, TempDir};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use chrono::Utc;
+
+    #[actix_web::test]
+    async fn test_messages_of_channel_only_in_redb() {
+        let dir = TempDir::new();
+        let state = web::Data::new(test_state(&dir).await);
+        let channel = test_channel(ChannelStatus::Stopped, ChannelSettings::default());
+        state.save_channel(&channel).await.unwrap();
+        state.save_message(&text_message(channel.id, "kept", Utc::now())).await.unwrap();
+        let app = init_service(App::new().app_data(state.clone()).service(get_channel_messages)).await;
+
+        let req = TestRequest::get().uri(&format!("/channels/{}/messages", channel.id)).to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let page: serde_json::Value = read_body_json(response).await;
+        assert_eq!(page["messages"][0]["content"], "kept");
+
+        let req = TestRequest::get().uri(&format!("/channels/{}/messages", Uuid::new_v4())).to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+}
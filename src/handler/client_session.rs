@@ -0,0 +1,526 @@
+use crate::error::AppError;
+use crate::handler::subscriber::authorize_channel;
+use crate::models::message::{BroadcastMessage, Delivery, FrameFormat, MessageSender, MessageType};
+use crate::models::protocol::{ClientCommand, ClientFrame, CommandError, ErrorCode, ServerFrame};
+use crate::models::channel::ChannelStatus;
+use crate::services::message_service::MessageService;
+use crate::state::{AppState, ClientMeta, Connection, SessionWriter, Sink};
+use crate::utils::rate_limit::RateLimitKey;
+use actix_web::web;
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, ProtocolError};
+use chrono::Utc;
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, Interval};
+use uuid::Uuid;
+
+/// Contenido de un mensaje enviado por un cliente
+pub enum ClientContent {
+    Text(String),
+    Json(serde_json::Value),
+    Binary { content_type: String, payload: Vec<u8> },
+}
+
+/// Permisos de la sesión en un canal, obtenidos al autorizar la suscripción
+#[derive(Debug, Clone)]
+pub struct ChannelGrant {
+    pub client_identity: String,
+    pub can_publish: bool,
+}
+
+/// Sesión WebSocket de un cliente: publica lo que envía y atiende sus comandos cuando
+/// negoció el protocolo v1. En `/channels/{id}/ws` sirve un único canal; en el hub
+/// cada comando indica su canal y la conexión puede suscribirse a varios.
+pub struct ClientSession {
+    pub state: web::Data<AppState>,
+    /// Único camino de escritura de la sesión, compartido con el fan-out
+    pub session: SessionWriter,
+    pub connection_id: Uuid,
+    pub meta: ClientMeta,
+    /// Canal de `/channels/{id}/ws`; `None` en el hub
+    pub home_channel: Option<Uuid>,
+    /// Canales autorizados y los permisos del token con que se autorizaron
+    pub grants: HashMap<Uuid, ChannelGrant>,
+    pub format: FrameFormat,
+    /// Los frames de texto son comandos v1; si no, se publican en `home_channel`
+    pub typed_commands: bool,
+    /// Tipo de contenido de los frames binarios que envíe el cliente
+    pub binary_content_type: String,
+    /// Emisor que llevan las suscripciones del hub; ver `Connection::revoked`
+    pub revoke: mpsc::UnboundedSender<Uuid>,
+    /// Canales de los que el servidor dio de baja a la conexión
+    pub revoked: mpsc::UnboundedReceiver<Uuid>,
+}
+
+impl ClientSession {
+    /// Atiende la conexión hasta que se cierra y la elimina de todos sus canales
+    pub async fn run(mut self, mut stream: AggregatedMessageStream) {
+        // Heartbeat del servidor (ping_interval = 0 lo deshabilita)
+        let pong_timeout = Duration::from_secs(self.state.config.websocket.pong_timeout);
+        let mut heartbeat = match self.state.config.websocket.ping_interval {
+            0 => None,
+            secs => {
+                let mut interval = tokio::time::interval(Duration::from_secs(secs));
+                interval.tick().await;
+                Some(interval)
+            }
+        };
+
+        let mut ping_payload: u64 = 0;
+        let mut ping_sent_at: Option<Instant> = None;
+        let mut pong_deadline: Option<Instant> = None;
+
+        loop {
+            tokio::select! {
+                msg = stream.next() => {
+                    match msg {
+                        Some(Ok(AggregatedMessage::Text(text))) => {
+                            // Solo el tamaño: un `subscribe` lleva el token de suscriptor
+                            tracing::debug!("Received {} text bytes on connection {}", text.len(), self.connection_id);
+
+                            match self.home_channel {
+                                Some(channel_id) if !self.typed_commands => {
+                                    if let Err(error) = self
+                                        .publish(channel_id, ClientContent::Text(text.to_string()), BTreeMap::new())
+                                        .await
+                                    {
+                                        self.send_error(None, Some(channel_id), error).await;
+                                    }
+                                }
+                                _ => self.handle_command(&text).await,
+                            }
+                        }
+                        Some(Ok(AggregatedMessage::Binary(bytes))) => {
+                            tracing::debug!("Received {} binary bytes on connection {}", bytes.len(), self.connection_id);
+
+                            // En el hub un frame binario no indica a qué canal va
+                            let result = match self.home_channel {
+                                Some(channel_id) => {
+                                    let content = ClientContent::Binary {
+                                        content_type: self.binary_content_type.clone(),
+                                        payload: bytes.to_vec(),
+                                    };
+                                    self.publish(channel_id, content, BTreeMap::new()).await.map(|_| ())
+                                }
+                                None => Err(CommandError::new(
+                                    ErrorCode::InvalidFrame,
+                                    "Binary frames are not supported on the hub; use a 'publish' command",
+                                )),
+                            };
+                            if let Err(error) = result {
+                                self.send_error(None, self.home_channel, error).await;
+                            }
+                        }
+                        Some(Ok(AggregatedMessage::Ping(msg))) => {
+                            let _ = self.session.pong(&msg).await;
+                        }
+                        Some(Ok(AggregatedMessage::Pong(payload))) => {
+                            if payload.as_ref() == ping_payload.to_be_bytes()
+                                && let Some(sent_at) = ping_sent_at.take()
+                            {
+                                let rtt_ms = sent_at.elapsed().as_secs_f64() * 1000.0;
+                                pong_deadline = None;
+                                self.state.record_rtt(&self.connection_id, rtt_ms).await;
+                                tracing::debug!("Connection {} rtt {:.2}ms", self.connection_id, rtt_ms);
+                            }
+                        }
+                        Some(Err(ProtocolError::Overflow)) => {
+                            // El frame supera el límite global; el stream no es recuperable
+                            let limit = self.state.config.message_size_limit;
+                            let error = CommandError::new(
+                                ErrorCode::MessageTooLarge,
+                                format!("Message exceeds limit of {} bytes", limit),
+                            )
+                            .with_details(serde_json::json!({ "limit": limit }));
+                            self.send_error(None, self.home_channel, error).await;
+                            self.session.close(Some(CloseReason {
+                                code: CloseCode::Size,
+                                description: Some("message too large".to_string()),
+                            }));
+                            break;
+                        }
+                        Some(Ok(AggregatedMessage::Close(_))) | None => {
+                            tracing::debug!("WebSocket connection {} closed", self.connection_id);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                Some(channel_id) = self.revoked.recv() => {
+                    // Baja forzada (canal borrado o límite reducido): hay que volver a presentar el token
+                    self.grants.remove(&channel_id);
+                }
+                _ = next_heartbeat(&mut heartbeat) => {
+                    // Un ping pendiente se mantiene hasta su deadline; no se solapan
+                    if ping_sent_at.is_some() {
+                        continue;
+                    }
+
+                    ping_payload = ping_payload.wrapping_add(1);
+                    if self.session.ping(&ping_payload.to_be_bytes()).await.is_err() {
+                        break;
+                    }
+
+                    let now = Instant::now();
+                    ping_sent_at = Some(now);
+                    pong_deadline = Some(now + pong_timeout);
+                }
+                _ = tokio::time::sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                    tracing::debug!("Connection {} missed pong, closing", self.connection_id);
+                    self.session.close(Some(CloseReason {
+                        code: CloseCode::Away,
+                        description: Some("pong timeout".to_string()),
+                    }));
+                    break;
+                }
+            }
+        }
+
+        // Limpiar conexión
+        let channels = self.state.remove_client(&self.connection_id).await;
+        tracing::debug!("Cleaned up connection {} from {} channel(s)", self.connection_id, channels);
+    }
+
+    /// Atiende un frame de texto del protocolo v1 y responde con su resultado o error
+    pub async fn handle_command(&mut self, text: &str) {
+        let frame = match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(value) => {
+                let id = value.get("id").cloned();
+                serde_json::from_value::<ClientFrame>(value).map_err(|e| (id, e))
+            }
+            Err(e) => Err((None, e)),
+        };
+
+        let frame = match frame {
+            Ok(frame) => frame,
+            Err((id, e)) => {
+                let error = CommandError::new(ErrorCode::InvalidFrame, format!("Invalid frame: {}", e));
+                self.send_error(id, self.home_channel, error).await;
+                return;
+            }
+        };
+
+        let channel_id = frame.command.channel_id().or(self.home_channel);
+        match self.execute(frame.command).await {
+            Ok(result) => self.send_frame(&ServerFrame::Result { id: frame.id, result }).await,
+            Err(error) => self.send_error(Some(frame.id), channel_id, error).await,
+        }
+    }
+
+    async fn execute(&mut self, command: ClientCommand) -> Result<serde_json::Value, CommandError> {
+        match command {
+            ClientCommand::Subscribe { channel_id, since_seq, token } => {
+                let channel_id = self.resolve_channel(channel_id)?;
+                self.subscribe(channel_id, since_seq, token.as_deref()).await
+            }
+            ClientCommand::Unsubscribe { channel_id } => {
+                let channel_id = self.resolve_channel(channel_id)?;
+                if self.state.remove_connection(&channel_id, &self.connection_id).await.is_none() {
+                    return Err(not_subscribed(channel_id));
+                }
+
+                // En el hub hay que volver a presentar el token para suscribirse de nuevo
+                if self.home_channel.is_none() {
+                    self.grants.remove(&channel_id);
+                }
+                Ok(serde_json::json!({ "channel_id": channel_id, "subscribed": false }))
+            }
+            ClientCommand::Publish { channel_id, content, data, headers } => {
+                let channel_id = self.resolve_channel(channel_id)?;
+
+                let content = match (content, data) {
+                    (Some(content), None) => ClientContent::Text(content),
+                    (None, Some(data)) => ClientContent::Json(data),
+                    _ => {
+                        return Err(CommandError::new(
+                            ErrorCode::InvalidFrame,
+                            "Provide exactly one of 'content' or 'data'",
+                        ))
+                    }
+                };
+                MessageService::validate_headers(&headers)
+                    .map_err(|e| CommandError::new(ErrorCode::InvalidFrame, e.to_string()))?;
+
+                let (message, sent_count, delivery) = self.publish(channel_id, content, headers).await?;
+                Ok(serde_json::json!({
+                    "channel_id": channel_id,
+                    "message_id": message.id,
+                    "seq": (delivery == Delivery::Delivered).then_some(message.seq),
+                    "sent_to": sent_count,
+                    "delivery": delivery
+                }))
+            }
+            ClientCommand::Ack { channel_id, seq } => {
+                let channel_id = self.resolve_channel(channel_id)?;
+                if !self.state.record_ack(&channel_id, &self.connection_id, seq).await {
+                    return Err(not_subscribed(channel_id));
+                }
+                Ok(serde_json::json!({ "channel_id": channel_id, "seq": seq }))
+            }
+            ClientCommand::Ping => Ok(serde_json::json!({
+                "pong": true,
+                "timestamp": Utc::now(),
+                "subscriptions": self.state.subscriptions(&self.connection_id).await
+            })),
+            ClientCommand::Presence { channel_id } => {
+                let channel_id = self.resolve_channel(channel_id)?;
+                if !self.grants.contains_key(&channel_id) {
+                    return Err(not_subscribed(channel_id));
+                }
+
+                let connections = self.state.list_presence(&channel_id).await;
+                Ok(serde_json::json!({
+                    "channel_id": channel_id,
+                    "count": connections.len(),
+                    "connections": connections
+                }))
+            }
+        }
+    }
+
+    /// Registra la sesión en el canal; con `since_seq` reenvía lo perdido antes del tráfico en vivo.
+    /// En el hub cada suscripción se autoriza con su propio token.
+    async fn subscribe(
+        &mut self,
+        channel_id: Uuid,
+        since_seq: Option<u64>,
+        token: Option<&str>,
+    ) -> Result<serde_json::Value, CommandError> {
+        // Cada suscripción del hub cuesta memoria y fan-out, así que se limitan por conexión
+        let max_subscriptions = self.state.config.websocket.max_subscriptions_per_connection;
+        if self.home_channel.is_none() && max_subscriptions > 0 {
+            let subscriptions = self.state.subscriptions(&self.connection_id).await;
+            if subscriptions.len() >= max_subscriptions && !subscriptions.contains(&channel_id) {
+                return Err(CommandError::new(ErrorCode::ConnectionLimit, "Subscription limit reached")
+                    .with_details(serde_json::json!({
+                        "max_subscriptions": max_subscriptions,
+                        "current_subscriptions": subscriptions.len()
+                    })));
+            }
+        }
+
+        let channel = if self.home_channel == Some(channel_id) {
+            self.state
+                .get_channel(&channel_id)
+                .await
+                .filter(|channel| channel.status == ChannelStatus::Active)
+                .ok_or_else(|| CommandError::new(ErrorCode::ChannelUnavailable, "Channel not found or not active"))?
+        } else {
+            let access = authorize_channel(&self.state, channel_id, token)
+                .await
+                .map_err(|denied| denied.into_command_error())?;
+            self.grants.insert(
+                channel_id,
+                ChannelGrant { client_identity: access.client_identity, can_publish: access.can_publish },
+            );
+            access.channel
+        };
+
+        if self.state.is_subscribed(&channel_id, &self.connection_id).await {
+            if since_seq.is_none() {
+                return self.subscription_status(channel_id, 0).await;
+            }
+            // Se vuelve a registrar en modo `replaying` para reenviar desde la nueva secuencia
+            self.state.remove_connection(&channel_id, &self.connection_id).await;
+        }
+
+        let client_identity = self
+            .grants
+            .get(&channel_id)
+            .map(|grant| grant.client_identity.clone())
+            .unwrap_or_default();
+        let mut connection = Connection::new(self.connection_id, client_identity, Sink::WebSocket(self.session.clone()));
+        connection.replaying = since_seq.is_some();
+        connection.format = self.format;
+        connection.meta = self.meta.clone();
+        connection.multiplexed = self.home_channel.is_none();
+        if connection.multiplexed {
+            connection.revoked = Some(self.revoke.clone());
+        }
+
+        let max_connections = self.state.max_connections_for(&channel);
+        self.state
+            .try_add_connection(channel_id, connection, max_connections)
+            .await
+            .map_err(|current| {
+                CommandError::new(ErrorCode::ConnectionLimit, "Channel is full").with_details(serde_json::json!({
+                    "max_connections": max_connections,
+                    "current_connections": current
+                }))
+            })?;
+
+        let replayed = match since_seq {
+            Some(since_seq) => self
+                .state
+                .replay_missed(&channel, &self.connection_id, &mut Sink::WebSocket(self.session.clone()), self.format, since_seq)
+                .await
+                .map_err(|e| CommandError::new(ErrorCode::Internal, format!("Replay failed: {}", e)))?,
+            None => 0,
+        };
+
+        self.subscription_status(channel_id, replayed).await
+    }
+
+    async fn subscription_status(&self, channel_id: Uuid, replayed: usize) -> Result<serde_json::Value, CommandError> {
+        let last_seq = self
+            .state
+            .last_seq(&channel_id)
+            .await
+            .map_err(|e| CommandError::new(ErrorCode::Internal, e.to_string()))?;
+
+        Ok(serde_json::json!({
+            "channel_id": channel_id,
+            "subscribed": true,
+            "last_seq": last_seq,
+            "replayed": replayed
+        }))
+    }
+
+    /// Aplica los permisos y límites del canal a un mensaje de cliente y lo publica
+    pub async fn publish(
+        &self,
+        channel_id: Uuid,
+        content: ClientContent,
+        headers: BTreeMap<String, String>,
+    ) -> Result<(BroadcastMessage, usize, Delivery), CommandError> {
+        let grant = self.grants.get(&channel_id).ok_or_else(|| not_subscribed(channel_id))?;
+        let channel = self
+            .state
+            .get_channel(&channel_id)
+            .await
+            .ok_or_else(|| CommandError::new(ErrorCode::ChannelUnavailable, "Channel not found"))?;
+
+        // Misma política de pausa que las publicaciones REST
+        let accepted_while_paused = MessageService::accepts_publish(&channel)
+            .map_err(|e| CommandError::new(ErrorCode::ChannelUnavailable, e.to_string()))?;
+
+        // Verificar si el canal permite mensajes de clientes
+        if !channel.settings.allow_client_messages {
+            return Err(CommandError::new(
+                ErrorCode::ClientMessagesDisabled,
+                "Channel does not accept client messages",
+            ));
+        }
+
+        if !grant.can_publish {
+            return Err(CommandError::new(ErrorCode::Forbidden, "Token does not allow publishing"));
+        }
+
+        let mut message = BroadcastMessage {
+            id: Uuid::new_v4(),
+            channel_id,
+            content: String::new(),
+            message_type: MessageType::ClientMessage,
+            sender: MessageSender::Client(grant.client_identity.clone()),
+            timestamp: Utc::now(),
+            seq: 0,
+            content_type: None,
+            payload: None,
+            data: None,
+            headers,
+        };
+        match content {
+            ClientContent::Text(text) => message.content = text,
+            ClientContent::Json(data) => message.data = Some(data),
+            ClientContent::Binary { content_type, payload } => {
+                message.content_type = Some(content_type);
+                message.payload = Some(payload);
+            }
+        }
+
+        // Tamaño máximo del canal (nunca mayor que el global)
+        let size_limit = self.state.message_size_limit_for(&channel);
+        if message.size() > size_limit {
+            return Err(CommandError::new(
+                ErrorCode::MessageTooLarge,
+                AppError::PayloadTooLarge { size: message.size(), limit: size_limit }.to_string(),
+            )
+            .with_details(serde_json::json!({ "size": message.size(), "limit": size_limit })));
+        }
+
+        // Límite por conexión
+        if let Some(per_minute) = channel.settings.rate_limit_per_minute
+            && let Err(retry_after) = self
+                .state
+                .rate_limiter
+                .check(RateLimitKey::Connection(self.connection_id), per_minute)
+        {
+            return Err(CommandError::new(ErrorCode::RateLimited, "Rate limit exceeded").with_details(
+                serde_json::json!({
+                    "limit_per_minute": per_minute,
+                    "retry_after_ms": retry_after.as_millis() as u64
+                }),
+            ));
+        }
+
+        if accepted_while_paused {
+            let held = MessageService::hold_while_paused(&self.state, &message).await.map_err(|e| {
+                let error = CommandError::new(ErrorCode::ChannelUnavailable, e.to_string());
+                match e.downcast_ref::<AppError>() {
+                    Some(AppError::PauseBufferFull { max_messages, max_bytes }) => error.with_details(
+                        serde_json::json!({ "max_messages": max_messages, "max_bytes": max_bytes }),
+                    ),
+                    _ => error,
+                }
+            })?;
+            if let Some(delivery) = held {
+                return Ok((message, 0, delivery));
+            }
+        }
+
+        // Numerar, persistir si está configurado y reenviar a todos los clientes del canal
+        let (message, sent_count) = self.state.publish(&channel, message).await.map_err(|e| {
+            tracing::warn!("Failed to publish client message in channel {}: {}", channel_id, e);
+            CommandError::new(ErrorCode::Internal, "Failed to publish message")
+        })?;
+        Ok((message, sent_count, Delivery::Delivered))
+    }
+
+    /// Envía un error solo a esta sesión, en el formato de la conexión.
+    /// Los clientes legacy solo existen en `/channels/{id}/ws`, así que siempre hay canal.
+    pub async fn send_error(&mut self, id: Option<serde_json::Value>, channel_id: Option<Uuid>, error: CommandError) {
+        let json = match self.format {
+            FrameFormat::V1 => ServerFrame::Error { id, channel_id, error }.to_json(),
+            FrameFormat::Legacy => serde_json::to_string(&error.to_legacy(channel_id.unwrap_or_default())),
+        };
+
+        if let Ok(json) = json {
+            let _ = self.session.text(json).await;
+        }
+    }
+
+    async fn send_frame(&mut self, frame: &ServerFrame) {
+        if let Ok(json) = frame.to_json() {
+            let _ = self.session.text(json).await;
+        }
+    }
+
+    /// Canal al que va un comando: en el hub es obligatorio; en `/channels/{id}/ws` solo vale el propio
+    fn resolve_channel(&self, channel_id: Option<Uuid>) -> Result<Uuid, CommandError> {
+        match (self.home_channel, channel_id) {
+            (Some(home), None) => Ok(home),
+            (Some(home), Some(channel_id)) if channel_id != home => Err(CommandError::new(
+                ErrorCode::ChannelUnavailable,
+                format!("This connection only serves channel {}", home),
+            )),
+            (_, Some(channel_id)) => Ok(channel_id),
+            (None, None) => Err(CommandError::new(ErrorCode::InvalidFrame, "Missing 'channel_id'")),
+        }
+    }
+}
+
+/// Siguiente tick del heartbeat; sin ping configurado no llega nunca
+async fn next_heartbeat(heartbeat: &mut Option<Interval>) {
+    match heartbeat {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn not_subscribed(channel_id: Uuid) -> CommandError {
+    CommandError::new(ErrorCode::NotSubscribed, format!("Not subscribed to channel {}", channel_id))
+}
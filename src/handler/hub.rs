@@ -0,0 +1,72 @@
+use crate::handler::client_session::ClientSession;
+use crate::handler::subscriber::{client_meta, rejected_origin};
+use crate::handler::websocket::offers_protocol;
+use crate::models::message::{FrameFormat, DEFAULT_BINARY_CONTENT_TYPE};
+use crate::models::protocol::{ServerFrame, PROTOCOL_V1};
+use crate::state::{AppState, SessionWriter};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{get, web, Error, HttpRequest, HttpResponse, Result};
+use chrono::Utc;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Hub WebSocket: una sola conexión se suscribe y se da de baja de varios canales con
+/// comandos del protocolo v1. Cada frame lleva el `channel_id` al que pertenece y, con
+/// autenticación, cada `subscribe` presenta el token de suscriptor de su canal.
+#[get("/ws")]
+async fn hub_handler(
+    req: HttpRequest,
+    stream: web::Payload,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, Error> {
+    if let Some(origin) = rejected_origin(&req, &state) {
+        return Ok(HttpResponse::Forbidden().json(format!("Error: Origin {} is not allowed", origin)));
+    }
+
+    let (mut res, session, stream) = actix_ws::handle(&req, stream)?;
+    let session = SessionWriter::new(session);
+    if offers_protocol(&req, PROTOCOL_V1) {
+        res.headers_mut()
+            .insert(header::SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(PROTOCOL_V1));
+    }
+
+    // La conexión no se registra en ningún canal hasta su primer `subscribe`
+    let connection_id = Uuid::new_v4();
+    let welcome = ServerFrame::Event {
+        event: "connected".to_string(),
+        channel_id: None,
+        timestamp: Utc::now(),
+        message: "Connected to hub".to_string(),
+        data: Some(serde_json::json!({
+            "connection_id": connection_id,
+            "protocol": PROTOCOL_V1
+        })),
+    };
+    let _ = session.text(welcome.to_json()?).await;
+
+    let stream = stream
+        .max_frame_size(state.config.message_size_limit)
+        .aggregate_continuations()
+        .max_continuation_size(state.config.websocket.max_continuation_size);
+
+    let (revoke, revoked) = mpsc::unbounded_channel();
+    let client = ClientSession {
+        state: state.clone(),
+        session,
+        connection_id,
+        meta: client_meta(&req),
+        home_channel: None,
+        grants: HashMap::new(),
+        format: FrameFormat::V1,
+        typed_commands: true,
+        binary_content_type: DEFAULT_BINARY_CONTENT_TYPE.to_string(),
+        revoke,
+        revoked,
+    };
+    // Se puede consultar por id desde ya; `run` la da de baja al cerrarse
+    state.register_client(connection_id, client.meta.clone()).await;
+    actix_web::rt::spawn(client.run(stream));
+
+    Ok(res)
+}
@@ -1,3 +1,10 @@
+pub mod admin;
+pub mod auth;
 pub mod channel;
+pub mod client_session;
+pub mod poll;
+pub mod sse;
+pub mod subscriber;
 pub mod websocket;
-pub mod health;
\ No newline at end of file
+pub mod health;
+pub mod hub;
\ No newline at end of file
@@ -0,0 +1,208 @@
+use crate::handler::subscriber::{authorize_subscriber, client_meta, connection_limit_response, SubscriberAccess};
+use crate::models::message::{FrameFormat, PollBatch};
+use crate::state::{AppState, Connection, PolledFrame, Sink};
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const DEFAULT_POLL_TIMEOUT_SECS: u64 = 30;
+/// Un `timeout=0` haría que el cliente sondee sin pausa
+const MIN_POLL_TIMEOUT_SECS: u64 = 1;
+const MAX_POLL_TIMEOUT_SECS: u64 = 60;
+/// Frames máximos por respuesta; el resto se recoge en la siguiente petición
+const POLL_BATCH_LIMIT: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct PollQuery {
+    pub token: Option<String>,
+    /// Última secuencia recibida; sin cursor solo se esperan mensajes nuevos
+    pub cursor: Option<u64>,
+    /// Segundos que se retiene la petición si no hay mensajes (entre 1 y 60)
+    pub timeout: Option<u64>,
+    /// Formato de los frames de mensajes: `legacy` (por defecto) o `v1`
+    #[serde(default)]
+    pub format: FrameFormat,
+}
+
+/// Long-polling para clientes que no pueden mantener un stream abierto. Mientras espera,
+/// la petición se registra como una conexión más del canal y recibe por el mismo fan-out.
+#[get("/channels/{channel_id}/poll")]
+async fn poll_channel(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<PollQuery>,
+) -> Result<HttpResponse> {
+    let channel_id = path.into_inner();
+    let query = query.into_inner();
+
+    let access = match authorize_subscriber(&req, &state, channel_id, query.token.as_deref()).await {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
+    };
+    let SubscriberAccess { channel, client_identity, .. } = access;
+
+    let last_seq = state
+        .last_seq(&channel_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let cursor = query.cursor.unwrap_or(last_seq);
+    let wait = Duration::from_secs(
+        query
+            .timeout
+            .unwrap_or(DEFAULT_POLL_TIMEOUT_SECS)
+            .clamp(MIN_POLL_TIMEOUT_SECS, MAX_POLL_TIMEOUT_SECS),
+    );
+
+    let (sender, mut receiver) = mpsc::channel::<PolledFrame>(POLL_BATCH_LIMIT);
+    let connection_id = Uuid::new_v4();
+    let max_connections = state.max_connections_for(&channel);
+
+    // Se registra en modo `replaying` para que lo posterior al cursor llegue antes que el tráfico en vivo
+    let mut connection = Connection::new(connection_id, client_identity, Sink::Poll(sender.clone()));
+    connection.replaying = true;
+    connection.format = query.format;
+    connection.meta = client_meta(&req);
+
+    if let Err(current) = state
+        .try_add_connection(channel_id, connection, max_connections)
+        .await
+    {
+        return Ok(connection_limit_response(&channel, max_connections, current));
+    }
+
+    let mut sink = Sink::Poll(sender);
+    let replay = state.replay_missed(&channel, &connection_id, &mut sink, query.format, cursor);
+
+    // Al terminar se suelta el receptor: si el reenvío sigue bloqueado en un canal lleno, falla y acaba
+    let collect = async move {
+        let mut frames = Vec::new();
+
+        if let Ok(Some(frame)) = tokio::time::timeout(wait, receiver.recv()).await {
+            frames.push(frame);
+            while frames.len() < POLL_BATCH_LIMIT {
+                match receiver.try_recv() {
+                    Ok(frame) => frames.push(frame),
+                    Err(_) => break,
+                }
+            }
+        }
+
+        frames
+    };
+
+    let (_, frames) = tokio::join!(replay, collect);
+    state.remove_connection(&channel_id, &connection_id).await;
+
+    let next_cursor = next_cursor(&frames, cursor, last_seq);
+    let events = frames
+        .iter()
+        .map(|frame| serde_json::from_str(&frame.frame))
+        .collect::<serde_json::Result<Vec<_>>>()?;
+
+    Ok(HttpResponse::Ok().json(PollBatch { events, next_cursor }))
+}
+
+/// Cursor de la siguiente petición: la mayor secuencia del lote. Los huecos llevan su punto de
+/// reanudación, así que un lote solo con el `gap` también avanza o corrige un cursor adelantado.
+fn next_cursor(frames: &[PolledFrame], cursor: u64, last_seq: u64) -> u64 {
+    frames
+        .iter()
+        .filter_map(|frame| frame.seq)
+        .max()
+        .unwrap_or(cursor.min(last_seq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::channel::{ChannelSettings, ChannelStatus};
+    use crate::utils::test_support::{loaded_channel, test_state, text_message, TempDir};
+    use actix_web::test::{call_and_read_body_json, call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chrono::Utc;
+    use std::time::Instant;
+
+    fn frame(seq: Option<u64>) -> PolledFrame {
+        PolledFrame { seq, frame: "{}".to_string() }
+    }
+
+    #[test]
+    fn test_next_cursor() {
+        // Sin frames el cursor se mantiene, o baja a la última secuencia si iba por delante
+        assert_eq!(next_cursor(&[], 3, 5), 3);
+        assert_eq!(next_cursor(&[], 9, 5), 5);
+
+        // Un lote solo con el hueco avanza hasta su punto de reanudación
+        assert_eq!(next_cursor(&[frame(Some(5))], 2, 5), 5);
+
+        // Los frames de control no llevan secuencia
+        assert_eq!(next_cursor(&[frame(Some(3)), frame(None), frame(Some(4))], 2, 5), 4);
+    }
+
+    async fn active_channel(state: &AppState, settings: ChannelSettings) -> Uuid {
+        loaded_channel(state, ChannelStatus::Active, settings).await.id
+    }
+
+    #[actix_web::test]
+    async fn test_poll_returns_backlog_and_cursor() {
+        let dir = TempDir::new();
+        let state = web::Data::new(test_state(&dir).await);
+        let settings = ChannelSettings { persist_messages: true, ..ChannelSettings::default() };
+        let channel_id = active_channel(&state, settings).await;
+        let channel = state.get_channel(&channel_id).await.unwrap();
+        for i in 0..3 {
+            state.publish(&channel, text_message(channel_id, &format!("m{}", i), Utc::now())).await.unwrap();
+        }
+        let app = init_service(App::new().app_data(state.clone()).service(poll_channel)).await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/channels/{}/poll?cursor=1&timeout=1", channel_id))
+            .to_request();
+        let batch: PollBatch = call_and_read_body_json(&app, req).await;
+
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.next_cursor, 3);
+        assert_eq!(state.connections.lock().await.count(&channel_id), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_poll_timeout_has_a_minimum() {
+        let dir = TempDir::new();
+        let state = web::Data::new(test_state(&dir).await);
+        let channel_id = active_channel(&state, ChannelSettings::default()).await;
+        let app = init_service(App::new().app_data(state.clone()).service(poll_channel)).await;
+
+        let started = Instant::now();
+        let req = TestRequest::get()
+            .uri(&format!("/channels/{}/poll?timeout=0", channel_id))
+            .to_request();
+        let batch: PollBatch = call_and_read_body_json(&app, req).await;
+
+        assert!(batch.events.is_empty());
+        assert!(started.elapsed() >= Duration::from_secs(MIN_POLL_TIMEOUT_SECS));
+    }
+
+    #[actix_web::test]
+    async fn test_poll_respects_connection_limit() {
+        let dir = TempDir::new();
+        let state = web::Data::new(test_state(&dir).await);
+        let settings = ChannelSettings { max_connections: 1, ..ChannelSettings::default() };
+        let channel_id = active_channel(&state, settings).await;
+
+        let (sender, _receiver) = mpsc::channel(1);
+        let waiting = Connection::new(Uuid::new_v4(), "other".to_string(), Sink::Poll(sender));
+        state.connections.lock().await.insert(channel_id, waiting);
+        let app = init_service(App::new().app_data(state.clone()).service(poll_channel)).await;
+
+        let req = TestRequest::get()
+            .uri(&format!("/channels/{}/poll?timeout=1", channel_id))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        assert_eq!(response.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(state.connections.lock().await.count(&channel_id), 1);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::handler::subscriber::{authorize_subscriber, client_meta, connection_limit_response, SubscriberAccess};
+use crate::models::message::{FrameFormat, WebSocketResponse};
+use crate::state::{AppState, Connection, Sink};
+use crate::utils::sse::{event_stream, try_send_event, SseEvent, SSE_CHANNEL_CAPACITY};
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use chrono::Utc;
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+const LAST_EVENT_ID: &str = "Last-Event-ID";
+
+#[derive(Debug, Deserialize)]
+pub struct EventStreamQuery {
+    /// Token de suscriptor; `EventSource` no permite enviar headers
+    pub token: Option<String>,
+    /// Última secuencia recibida; `Last-Event-ID` tiene prioridad al reconectar
+    pub since_seq: Option<u64>,
+    /// Formato de los frames de mensajes: `legacy` (por defecto) o `v1`
+    #[serde(default)]
+    pub format: FrameFormat,
+}
+
+/// Alternativa a WebSocket para clientes detrás de proxies que cortan el upgrade.
+/// Recibe los mismos frames JSON por el mismo fan-out; cada mensaje lleva su secuencia en `id:`.
+#[get("/channels/{channel_id}/events")]
+async fn event_stream_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    path: web::Path<Uuid>,
+    query: web::Query<EventStreamQuery>,
+) -> Result<HttpResponse> {
+    let channel_id = path.into_inner();
+    let query = query.into_inner();
+
+    let access = match authorize_subscriber(&req, &state, channel_id, query.token.as_deref()).await {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
+    };
+    let SubscriberAccess { channel, client_identity, .. } = access;
+
+    // EventSource reenvía el último `id:` recibido al reconectar
+    let since_seq = req
+        .headers()
+        .get(LAST_EVENT_ID)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(query.since_seq);
+
+    let (sender, receiver) = mpsc::channel(SSE_CHANNEL_CAPACITY);
+    let connection_id = Uuid::new_v4();
+    let max_connections = state.max_connections_for(&channel);
+
+    let mut connection = Connection::new(connection_id, client_identity.clone(), Sink::Sse(sender.clone()));
+    connection.replaying = since_seq.is_some();
+    connection.format = query.format;
+    connection.meta = client_meta(&req);
+
+    if let Err(current) = state
+        .try_add_connection(channel_id, connection, max_connections)
+        .await
+    {
+        return Ok(connection_limit_response(&channel, max_connections, current));
+    }
+
+    // Enviar mensaje de bienvenida
+    let welcome_msg = WebSocketResponse {
+        status: "connected".to_string(),
+        message: format!("Connected to channel: {}", channel.name),
+        channel_id,
+        timestamp: Utc::now(),
+        seq: None,
+        data: Some(serde_json::json!({
+            "channel": channel,
+            "connection_id": connection_id,
+            "client_id": client_identity
+        })),
+    };
+
+    let mut sink = Sink::Sse(sender.clone());
+    let _ = sink.send(&welcome_msg.to_frame(query.format)?, None).await;
+
+    let state_clone = state.clone();
+    let format = query.format;
+    let keep_alive = match state.config.websocket.ping_interval {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
+
+    actix_web::rt::spawn(async move {
+        if let Some(since_seq) = since_seq
+            && let Err(e) = state_clone
+                .replay_missed(&channel, &connection_id, &mut sink, format, since_seq)
+                .await
+        {
+            tracing::debug!("Replay for SSE connection {} in channel {} failed: {}", connection_id, channel_id, e);
+            state_clone.remove_connection(&channel_id, &connection_id).await;
+            return;
+        }
+
+        // El receptor se suelta cuando el cliente se desconecta; el comentario periódico
+        // mantiene abierta la conexión a través de proxies
+        match keep_alive {
+            Some(period) => {
+                let mut heartbeat = tokio::time::interval(period);
+                heartbeat.tick().await;
+
+                loop {
+                    tokio::select! {
+                        _ = sender.closed() => break,
+                        _ = heartbeat.tick() => {
+                            // Con la cola llena el fan-out ya está dando de baja al cliente
+                            let _ = try_send_event(&sender, SseEvent::Comment("ping"));
+                        }
+                    }
+                }
+            }
+            None => sender.closed().await,
+        }
+
+        tracing::debug!("SSE connection {} closed for channel {}", connection_id, channel_id);
+        state_clone.remove_connection(&channel_id, &connection_id).await;
+    });
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, "text/event-stream"))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(event_stream(receiver)))
+}
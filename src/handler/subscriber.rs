@@ -0,0 +1,175 @@
+use crate::handler::auth::bearer_token;
+use crate::models::auth::TokenAction;
+use crate::models::channel::{Channel, ChannelStatus};
+use crate::models::message::WebSocketResponse;
+use crate::models::protocol::{CommandError, ErrorCode};
+use crate::services::token_service::TokenService;
+use crate::state::{AppState, ClientMeta};
+use crate::utils::cors::is_origin_allowed;
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::Utc;
+use uuid::Uuid;
+
+const ANONYMOUS_CLIENT: &str = "anonymous";
+
+/// Suscriptor ya validado, listo para abrir su transporte
+pub struct SubscriberAccess {
+    pub channel: Channel,
+    pub client_identity: String,
+    pub can_publish: bool,
+}
+
+/// Motivo por el que se rechaza una suscripción a un canal
+#[derive(Debug)]
+pub enum SubscriberDenied {
+    ChannelNotFound,
+    ChannelNotActive { name: String },
+    /// Token ausente, inválido o de otro canal
+    Unauthorized(String),
+    /// El token no incluye la acción `subscribe`
+    Forbidden,
+}
+
+impl SubscriberDenied {
+    fn into_response(self, channel_id: Uuid) -> HttpResponse {
+        let (status, message) = match &self {
+            SubscriberDenied::ChannelNotFound => ("error", "Channel not found".to_string()),
+            SubscriberDenied::ChannelNotActive { name } => ("denied", format!("Channel {} is not active", name)),
+            SubscriberDenied::Unauthorized(reason) => ("unauthorized", reason.clone()),
+            SubscriberDenied::Forbidden => ("denied", "Token does not allow subscribing".to_string()),
+        };
+
+        let error_response = WebSocketResponse {
+            status: status.to_string(),
+            message,
+            channel_id,
+            timestamp: Utc::now(),
+            seq: None,
+            data: None,
+        };
+
+        match self {
+            SubscriberDenied::ChannelNotFound => HttpResponse::NotFound().json(error_response),
+            SubscriberDenied::Unauthorized(_) => HttpResponse::Unauthorized().json(error_response),
+            _ => HttpResponse::Forbidden().json(error_response),
+        }
+    }
+
+    pub fn into_command_error(self) -> CommandError {
+        match self {
+            SubscriberDenied::ChannelNotFound => CommandError::new(ErrorCode::ChannelUnavailable, "Channel not found"),
+            SubscriberDenied::ChannelNotActive { name } => {
+                CommandError::new(ErrorCode::ChannelUnavailable, format!("Channel {} is not active", name))
+            }
+            SubscriberDenied::Unauthorized(reason) => CommandError::new(ErrorCode::Unauthorized, reason),
+            SubscriberDenied::Forbidden => CommandError::new(ErrorCode::Forbidden, "Token does not allow subscribing"),
+        }
+    }
+}
+
+/// Origen del navegador si no está permitido por la configuración CORS.
+/// Los navegadores envían Origin en el upgrade y en EventSource.
+pub fn rejected_origin<'a>(req: &'a HttpRequest, state: &AppState) -> Option<&'a str> {
+    let origin = req.headers().get(actix_web::http::header::ORIGIN)?;
+    let origin = origin.to_str().unwrap_or_default();
+
+    (!is_origin_allowed(&state.config.cors, origin)).then_some(origin)
+}
+
+/// Dirección y agente del cliente para el registro de conexiones
+pub fn client_meta(req: &HttpRequest) -> ClientMeta {
+    ClientMeta {
+        remote_addr: req.peer_addr().map(|addr| addr.to_string()),
+        user_agent: req
+            .headers()
+            .get(actix_web::http::header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+    }
+}
+
+/// Comprobaciones comunes a todos los transportes de suscripción: origen permitido,
+/// canal activo y, con autenticación, un token de suscriptor válido para el canal.
+/// `token` viene de la query; si falta se usa `Authorization: Bearer`.
+pub async fn authorize_subscriber(
+    req: &HttpRequest,
+    state: &AppState,
+    channel_id: Uuid,
+    token: Option<&str>,
+) -> Result<SubscriberAccess, HttpResponse> {
+    if let Some(origin) = rejected_origin(req, state) {
+        let error_response = WebSocketResponse {
+            status: "denied".to_string(),
+            message: format!("Origin {} is not allowed", origin),
+            channel_id,
+            timestamp: Utc::now(),
+            seq: None,
+            data: None,
+        };
+        return Err(HttpResponse::Forbidden().json(error_response));
+    }
+
+    authorize_channel(state, channel_id, token.or_else(|| bearer_token(req)))
+        .await
+        .map_err(|denied| denied.into_response(channel_id))
+}
+
+/// Canal activo y, con autenticación, token de suscriptor válido para él
+pub async fn authorize_channel(
+    state: &AppState,
+    channel_id: Uuid,
+    token: Option<&str>,
+) -> Result<SubscriberAccess, SubscriberDenied> {
+    let channel = state
+        .get_channel(&channel_id)
+        .await
+        .ok_or(SubscriberDenied::ChannelNotFound)?;
+
+    if channel.status != ChannelStatus::Active {
+        return Err(SubscriberDenied::ChannelNotActive { name: channel.name });
+    }
+
+    // Con autenticación activa se exige un token de suscriptor firmado para este canal
+    let claims = if state.config.auth.enabled {
+        let claims = token
+            .ok_or_else(|| anyhow::anyhow!("Missing subscriber token"))
+            .and_then(|token| TokenService::verify(state, token, &channel_id))
+            .map_err(|e| SubscriberDenied::Unauthorized(e.to_string()))?;
+
+        if !claims.allows(TokenAction::Subscribe) {
+            return Err(SubscriberDenied::Forbidden);
+        }
+
+        Some(claims)
+    } else {
+        None
+    };
+
+    let client_identity = claims
+        .as_ref()
+        .map_or_else(|| ANONYMOUS_CLIENT.to_string(), |claims| claims.sub.clone());
+    let can_publish = claims
+        .as_ref()
+        .is_none_or(|claims| claims.allows(TokenAction::Publish));
+
+    Ok(SubscriberAccess { channel, client_identity, can_publish })
+}
+
+/// Respuesta 503 cuando el canal ya tiene `max_connections` suscriptores
+pub fn connection_limit_response(channel: &Channel, max_connections: usize, current: usize) -> HttpResponse {
+    let error_response = WebSocketResponse {
+        status: "full".to_string(),
+        message: format!("Channel {} has reached its connection limit", channel.name),
+        channel_id: channel.id,
+        timestamp: Utc::now(),
+        seq: None,
+        data: Some(serde_json::json!({
+            "max_connections": max_connections,
+            "connections": current
+        })),
+    };
+
+    HttpResponse::ServiceUnavailable()
+        .insert_header((actix_web::http::header::RETRY_AFTER, "30"))
+        .json(error_response)
+}
@@ -1,52 +1,84 @@
-use crate::models::message::{WebSocketResponse, MessageType, MessageSender, BroadcastMessage};
-use crate::state::AppState;
+use crate::handler::client_session::{ChannelGrant, ClientSession};
+use crate::handler::subscriber::{authorize_subscriber, client_meta, connection_limit_response, SubscriberAccess};
+use crate::models::message::{is_valid_content_type, FrameFormat, WebSocketResponse, DEFAULT_BINARY_CONTENT_TYPE};
+use crate::models::protocol::PROTOCOL_V1;
+use crate::state::{AppState, Connection, SessionWriter, Sink};
+use actix_web::http::header::{self, HeaderValue};
 use actix_web::{web, HttpRequest, HttpResponse, Result, Error, get, Responder};
-use actix_ws::AggregatedMessage;
-use futures_util::StreamExt;
+use serde::Deserialize;
+use std::collections::HashMap;
 
+use tokio::sync::mpsc;
 use uuid::Uuid;
 use chrono::Utc;
 
+#[derive(Debug, Deserialize)]
+pub struct WebSocketQuery {
+    /// Token de suscriptor; los navegadores no pueden enviar headers en el upgrade
+    pub token: Option<String>,
+    /// Última secuencia recibida; se reenvían los mensajes posteriores antes del tráfico en vivo
+    pub since_seq: Option<u64>,
+    /// Tipo de contenido de los frames binarios que envíe el cliente
+    pub content_type: Option<String>,
+    /// Formato de los frames de mensajes: `legacy` (por defecto) o `v1`. El protocolo v1 implica `v1`.
+    #[serde(default)]
+    pub format: FrameFormat,
+}
+
+/// Suscripción WebSocket a un canal. Si el cliente ofrece `emit-hub.v1` en
+/// `Sec-WebSocket-Protocol`, los frames de texto son comandos tipados; si no, cada
+/// texto recibido se publica en el canal (protocolo legacy).
 #[get("/channels/{channel_id}/ws")]
 async fn websocket_handler(
     req: HttpRequest,
     stream: web::Payload,
     state: web::Data<AppState>,
     path: web::Path<Uuid>,
+    query: web::Query<WebSocketQuery>,
 ) -> Result<HttpResponse, Error> {
     let channel_id = path.into_inner();
+    let query = query.into_inner();
 
-    // Verificar que el canal existe y está activo
-    let channel = match state.get_channel(&channel_id).await {
-        Some(channel) => channel,
-        None => {
-            let error_response = WebSocketResponse {
-                status: "error".to_string(),
-                message: "Channel not found".to_string(),
-                channel_id,
-                timestamp: Utc::now(),
-                data: None,
-            };
-            return Ok(HttpResponse::NotFound().json(error_response));
-        }
+    let access = match authorize_subscriber(&req, &state, channel_id, query.token.as_deref()).await {
+        Ok(access) => access,
+        Err(response) => return Ok(response),
     };
-
-    if !matches!(channel.status, crate::models::channel::ChannelStatus::Active) {
-        let error_response = WebSocketResponse {
-            status: "denied".to_string(),
-            message: format!("Channel {} is not active", channel.name),
-            channel_id,
-            timestamp: Utc::now(),
-            data: None,
-        };
-        return Ok(HttpResponse::Forbidden().json(error_response));
+    let SubscriberAccess { channel, client_identity, can_publish } = access;
+
+    let binary_content_type = query
+        .content_type
+        .clone()
+        .unwrap_or_else(|| DEFAULT_BINARY_CONTENT_TYPE.to_string());
+    if !is_valid_content_type(&binary_content_type) {
+        return Ok(HttpResponse::BadRequest().json(format!("Error: Invalid content type '{}'", binary_content_type)));
     }
 
+    let typed_commands = offers_protocol(&req, PROTOCOL_V1);
+    let format = if typed_commands { FrameFormat::V1 } else { query.format };
+
     // Establecer conexión WebSocket
-    let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let (mut res, session, stream) = actix_ws::handle(&req, stream)?;
+    let session = SessionWriter::new(session);
+    if typed_commands {
+        res.headers_mut()
+            .insert(header::SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(PROTOCOL_V1));
+    }
 
-    // Agregar conexión al estado
-    state.add_connection(channel_id, session.clone()).await;
+    // Agregar conexión al estado respetando el límite del canal
+    let connection_id = Uuid::new_v4();
+    let max_connections = state.max_connections_for(&channel);
+
+    let mut connection = Connection::new(connection_id, client_identity.clone(), Sink::WebSocket(session.clone()));
+    connection.replaying = query.since_seq.is_some();
+    connection.format = format;
+    connection.meta = client_meta(&req);
+
+    if let Err(current) = state
+        .try_add_connection(channel_id, connection, max_connections)
+        .await
+    {
+        return Ok(connection_limit_response(&channel, max_connections, current));
+    }
 
     // Enviar mensaje de bienvenida
     let welcome_msg = WebSocketResponse {
@@ -54,82 +86,67 @@ async fn websocket_handler(
         message: format!("Connected to channel: {}", channel.name),
         channel_id,
         timestamp: Utc::now(),
+        seq: None,
         data: Some(serde_json::json!({
             "channel": channel,
-            "connection_id": Uuid::new_v4()
+            "connection_id": connection_id,
+            "client_id": client_identity,
+            "protocol": typed_commands.then_some(PROTOCOL_V1)
         })),
     };
 
-    let welcome_json = serde_json::to_string(&welcome_msg)?;
+    let welcome_json = welcome_msg.to_frame(format)?;
     let _ = session.text(welcome_json).await;
 
     // Procesar mensajes entrantes
-    let mut stream = stream
+    let stream = stream
+        .max_frame_size(state.config.message_size_limit)
         .aggregate_continuations()
-        .max_continuation_size(2_usize.pow(20));
-    let state_clone = state.clone();
-    let mut session_clone = session.clone();
+        .max_continuation_size(state.config.websocket.max_continuation_size);
+    let since_seq = query.since_seq;
+
+    let (revoke, revoked) = mpsc::unbounded_channel();
+    let client = ClientSession {
+        state: state.clone(),
+        session: session.clone(),
+        connection_id,
+        meta: client_meta(&req),
+        home_channel: Some(channel_id),
+        grants: HashMap::from([(channel_id, ChannelGrant { client_identity, can_publish })]),
+        format,
+        typed_commands,
+        binary_content_type,
+        revoke,
+        revoked,
+    };
 
     actix_web::rt::spawn(async move {
-        while let Some(msg) = stream.next().await {
-            match msg {
-                Ok(AggregatedMessage::Text(text)) => {
-                    tracing::debug!("Received message in channel {}: {}", channel_id, text);
-
-                    // Verificar si el canal permite mensajes de clientes
-                    if let Some(current_channel) = state_clone.get_channel(&channel_id).await {
-                        if current_channel.settings.allow_client_messages {
-                            // Crear mensaje del cliente
-                            let client_message = BroadcastMessage {
-                                id: Uuid::new_v4(),
-                                channel_id,
-                                content: text.to_string(),
-                                message_type: MessageType::ClientMessage,
-                                sender: MessageSender::Client("anonymous".to_string()),
-                                timestamp: Utc::now(),
-                            };
-
-                            // Persistir si está configurado
-                            if current_channel.settings.persist_messages {
-                                let _ = state_clone.save_message(&client_message).await;
-                            }
-
-                            // Reenviar a todos los clientes del canal
-                            let response = WebSocketResponse {
-                                status: "client_message".to_string(),
-                                message: format!("Client message in {}: {}", current_channel.name, text),
-                                channel_id,
-                                timestamp: client_message.timestamp,
-                                data: Some(serde_json::json!({
-                                    "original_message": *text,
-                                    "sender": "client"
-                                })),
-                            };
-
-                            let json_response = serde_json::to_string(&response).unwrap();
-                            let _ = state_clone.broadcast_to_channel(&channel_id, &json_response).await;
-                        }
-                    }
-                }
-                Ok(AggregatedMessage::Ping(msg)) => {
-                    let _ = session_clone.pong(&msg).await;
-                }
-                Ok(AggregatedMessage::Close(_)) => {
-                    tracing::debug!("WebSocket connection closed for channel {}", channel_id);
-                    break;
-                }
-                _ => {}
-            }
+        // Reenviar lo perdido desde `since_seq` antes de recibir tráfico en vivo.
+        // Se hace aquí y no antes del upgrade porque la cola de la sesión es acotada.
+        if let Some(since_seq) = since_seq
+            && let Err(e) = state
+                .replay_missed(&channel, &connection_id, &mut Sink::WebSocket(session), format, since_seq)
+                .await
+        {
+            tracing::debug!("Replay for connection {} in channel {} failed: {}", connection_id, channel_id, e);
+            state.remove_connection(&channel_id, &connection_id).await;
+            return;
         }
 
-        // Limpiar conexión
-        state_clone.remove_connection(&channel_id, &session_clone).await;
-        tracing::debug!("Cleaned up connection for channel {}", channel_id);
+        client.run(stream).await;
     });
 
     Ok(res)
 }
 
+/// Indica si el cliente ofrece `protocol` en `Sec-WebSocket-Protocol`
+pub fn offers_protocol(req: &HttpRequest, protocol: &str) -> bool {
+    req.headers()
+        .get_all(header::SEC_WEBSOCKET_PROTOCOL)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|offered| offered.trim() == protocol)
+}
 
 #[get("/logs")]
 async fn logs_handler() -> impl Responder {
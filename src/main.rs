@@ -1,18 +1,34 @@
 use actix_web::{web, App, HttpServer, middleware::Logger};
+use clap::Parser;
 use std::sync::Arc;
 
 mod models;
 mod state;
 mod config;
+mod error;
+mod cli;
 mod handler;
 mod services;
 mod utils;
 
 use crate::state::AppState;
 use crate::config::Config;
-use crate::handler::channel::{broadcast_message, create_channel, get_channel, list_channels, pause_channel, start_channel, stop_channel};
+use crate::error::json_error_handler;
+use crate::cli::{Cli, Command, KeysCommand};
+use crate::handler::auth::{create_api_key, list_api_keys, redacted_request_line, revoke_api_key};
+use crate::handler::admin::{create_backup, get_retention_report, list_backups, run_retention};
+use crate::handler::channel::{broadcast_binary, broadcast_message, create_channel, create_subscriber_token, delete_channel, get_channel, get_channel_history, get_channel_messages, get_connection, list_channel_connections, list_channels, pause_channel, restore_channel, start_channel, stop_channel, update_channel};
 use crate::handler::health::{health_check, readiness_check};
+use crate::handler::hub::hub_handler;
+use crate::handler::poll::poll_channel;
+use crate::handler::sse::event_stream_handler;
 use crate::handler::websocket::{logs_handler, websocket_handler};
+use crate::services::auth_service::AuthService;
+use crate::services::backup_service::BackupService;
+use crate::services::retention_service::RetentionService;
+use crate::utils::cors::build_cors;
+
+const JSON_ENVELOPE_ALLOWANCE: usize = 16 * 1024;
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
@@ -23,33 +39,113 @@ async fn main() -> anyhow::Result<()> {
         .format_timestamp_secs()
         .init();
 
-    let config = Config::from_env()?;
-    let app_state = Arc::new(AppState::new(&config.db_path).await?);
+    let cli = Cli::parse();
+    let config = Config::load(cli.config.as_deref())?;
+    log::info!("Effective configuration:\n{}", config.to_toml()?);
+
+    if let Some(backup_name) = &cli.restore_backup {
+        BackupService::restore_backup(&config, backup_name)?;
+    }
+
+    let app_state = Arc::new(AppState::new(&config).await?);
+
+    if let Some(command) = cli.command {
+        return run_command(&app_state, command).await;
+    }
+
+    if config.auth.enabled && AuthService::list_keys(&app_state).await?.is_empty() {
+        log::warn!("Authentication is enabled but no API keys exist. Issue one with: emit-hub keys issue --name admin --scope admin");
+    }
+
+    RetentionService::spawn(app_state.clone());
+    BackupService::spawn(app_state.clone());
 
     log::info!("🚀 Starting EmitHub server on {}:{}", config.host, config.port);
 
+    let cors_config = config.cors.clone();
+    // El cuerpo JSON incluye campos además del contenido; el límite exacto se valida en el servicio
+    let json_limit = config.message_size_limit + JSON_ENVELOPE_ALLOWANCE;
+    let payload_limit = config.message_size_limit;
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::from(app_state.clone()))
-            .wrap(Logger::new("%a \"%r\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T"))
+            .app_data(web::JsonConfig::default().limit(json_limit).error_handler(json_error_handler))
+            .app_data(web::PayloadConfig::default().limit(payload_limit))
+            .wrap(build_cors(&cors_config))
+            // `%r` incluiría la query con el token de suscriptor; se registra enmascarado
+            .wrap(
+                Logger::new("%a \"%{request}xi\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T")
+                    .custom_request_replace("request", |req| {
+                        redacted_request_line(req.method().as_str(), req.path(), req.query_string())
+                    }),
+            )
             .service(logs_handler)
             .service(health_check)
             .service(readiness_check)
             .service(
                 web::scope("/api/v1")
                     .service(websocket_handler)
+                    .service(hub_handler)
+                    .service(event_stream_handler)
+                    .service(poll_channel)
                     .service(create_channel)
                     .service(list_channels)
                     .service(get_channel)
                     .service(start_channel)
                     .service(pause_channel)
                     .service(stop_channel)
+                    .service(restore_channel)
+                    .service(broadcast_binary)
                     .service(broadcast_message)
+                    .service(get_channel_messages)
+                    .service(list_channel_connections)
+                    .service(get_connection)
+                    .service(create_subscriber_token)
+                    .service(delete_channel)
+                    .service(update_channel)
+                    .service(get_channel_history)
+                    .service(get_retention_report)
+                    .service(run_retention)
+                    .service(create_backup)
+                    .service(list_backups)
+                    .service(create_api_key)
+                    .service(list_api_keys)
+                    .service(revoke_api_key)
             )
     })
         .bind((config.host, config.port))?
         .run()
         .await?;
 
+    Ok(())
+}
+
+/// Ejecuta un subcomando de administración y termina sin arrancar el servidor
+async fn run_command(state: &AppState, command: Command) -> anyhow::Result<()> {
+    match command {
+        Command::Keys(KeysCommand::Issue { name, scopes }) => {
+            let scopes = scopes
+                .iter()
+                .map(|scope| scope.parse())
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let issued = AuthService::issue_key(state, name, scopes).await?;
+            println!("API key {} ({}) created. Store it now, it will not be shown again:", issued.api_key.name, issued.api_key.id);
+            println!("{}", issued.key);
+        }
+        Command::Keys(KeysCommand::Revoke { id }) => {
+            let api_key = AuthService::revoke_key(state, id).await?;
+            println!("API key {} ({}) revoked", api_key.name, api_key.id);
+        }
+        Command::Keys(KeysCommand::List) => {
+            for api_key in AuthService::list_keys(state).await? {
+                let scopes: Vec<_> = api_key.scopes.iter().map(|scope| scope.as_str()).collect();
+                let status = if api_key.revoked_at.is_some() { "revoked" } else { "active" };
+                println!("{}  {}  {}...  [{}]  {}", api_key.id, api_key.name, api_key.prefix, scopes.join(", "), status);
+            }
+        }
+    }
+
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+/// Resultado de una ejecución de la limpieza de mensajes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetentionReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub retention_days: u32,
+    pub max_messages_per_channel: usize,
+    /// Mensajes eliminados por superar la ventana de retención
+    pub expired_removed: usize,
+    /// Mensajes eliminados por superar el límite del canal
+    pub trimmed_removed: usize,
+    pub channels_trimmed: usize,
+}
+
+impl RetentionReport {
+    pub fn total_removed(&self) -> usize {
+        self.expired_removed + self.trimmed_removed
+    }
+}
+
+/// Copia de la base de datos guardada junto a `db_path`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    pub name: String,
+    pub size_bytes: u64,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::str::FromStr;
+
+/// Permisos que puede tener una API key. `admin` incluye todos los demás.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "channels:read")]
+    ChannelsRead,
+    #[serde(rename = "channels:write")]
+    ChannelsWrite,
+    #[serde(rename = "broadcast")]
+    Broadcast,
+    #[serde(rename = "tokens:issue")]
+    TokensIssue,
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::ChannelsRead => "channels:read",
+            Scope::ChannelsWrite => "channels:write",
+            Scope::Broadcast => "broadcast",
+            Scope::TokensIssue => "tokens:issue",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "channels:read" => Ok(Scope::ChannelsRead),
+            "channels:write" => Ok(Scope::ChannelsWrite),
+            "broadcast" => Ok(Scope::Broadcast),
+            "tokens:issue" => Ok(Scope::TokensIssue),
+            "admin" => Ok(Scope::Admin),
+            other => Err(anyhow::anyhow!("Unknown scope '{}'", other)),
+        }
+    }
+}
+
+/// API key guardada en redb. El secreto nunca se guarda, solo su hash SHA-256.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    /// Primeros caracteres del secreto, para identificar la key en listados
+    pub prefix: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.revoked_at.is_none()
+            && self.scopes.iter().any(|s| *s == scope || *s == Scope::Admin)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Respuesta de emisión: el secreto en claro solo se muestra una vez
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssuedApiKey {
+    pub key: String,
+    pub api_key: ApiKey,
+}
+
+/// Acciones que un token de suscriptor permite sobre su canal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenAction {
+    Subscribe,
+    Publish,
+}
+
+/// Contenido firmado de un token de suscriptor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriberClaims {
+    pub channel_id: Uuid,
+    /// Identidad del cliente; se usa como `MessageSender::Client`
+    pub sub: String,
+    /// Expiración en segundos Unix
+    pub exp: i64,
+    pub actions: Vec<TokenAction>,
+}
+
+impl SubscriberClaims {
+    pub fn allows(&self, action: TokenAction) -> bool {
+        self.actions.contains(&action)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateSubscriberTokenRequest {
+    pub client_id: String,
+    pub actions: Option<Vec<TokenAction>>,
+    pub ttl_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriberToken {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub claims: SubscriberClaims,
+}
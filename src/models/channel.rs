@@ -11,14 +11,73 @@ pub struct Channel {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub settings: ChannelSettings,
+    /// Profundidad del buffer de pausa. Solo vive en memoria: ni se guarda ni se lee de redb.
+    #[serde(skip_deserializing)]
+    pub buffered: BufferDepth,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferDepth {
+    pub messages: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ChannelStatus {
     Created,
     Active,
     Paused,
     Stopped,
+    /// Borrado lógico: se conserva en la base de datos pero no en memoria
+    Archived,
+    /// Borrado definitivo. Solo aparece como último registro del historial:
+    /// ningún canal guardado tiene este estado.
+    Deleted,
+}
+
+impl ChannelStatus {
+    /// Transiciones permitidas del ciclo de vida. Un canal detenido o archivado
+    /// no vuelve a arrancar directamente: primero se restaura a `Created`.
+    pub fn can_transition_to(&self, next: ChannelStatus) -> bool {
+        use ChannelStatus::*;
+
+        matches!(
+            (self, next),
+            (Created, Active | Stopped | Archived)
+                | (Active, Paused | Stopped | Archived)
+                | (Paused, Active | Stopped | Archived)
+                | (Stopped, Created | Archived)
+                | (Archived, Created)
+                | (Created | Active | Paused | Stopped | Archived, Deleted)
+        )
+    }
+
+    /// Estados que se mantienen en el registro en memoria al arrancar
+    pub fn is_live(&self) -> bool {
+        matches!(self, ChannelStatus::Created | ChannelStatus::Active | ChannelStatus::Paused)
+    }
+}
+
+/// Registro de un cambio de estado de un canal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelTransition {
+    pub from: ChannelStatus,
+    pub to: ChannelStatus,
+    pub at: DateTime<Utc>,
+    /// Id de la API key que solicitó el cambio, o `anonymous` sin autenticación
+    pub actor: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ListChannelsQuery {
+    /// Sin filtro se listan todos los canales salvo los archivados
+    pub status: Option<ChannelStatus>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TransitionQuery {
+    pub reason: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,7 +85,27 @@ pub struct ChannelSettings {
     pub max_connections: usize,
     pub allow_client_messages: bool,
     pub persist_messages: bool,
+    /// Mensajes por minuto permitidos (por canal y por conexión). `None` o `0` = sin límite
     pub rate_limit_per_minute: Option<u32>,
+    /// Tamaño máximo de mensaje para este canal; no puede superar el límite global
+    #[serde(default)]
+    pub message_size_limit: Option<usize>,
+    /// Qué hacer con los mensajes publicados mientras el canal está en pausa
+    #[serde(default)]
+    pub pause_policy: PausePolicy,
+}
+
+/// Política de publicación para canales en pausa
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum PausePolicy {
+    /// Rechaza la publicación con error
+    #[default]
+    Reject,
+    /// Acepta la publicación y la descarta
+    Drop,
+    /// Retiene hasta `max_messages` mensajes o `max_bytes` bytes y los entrega al reanudar
+    Buffer { max_messages: usize, max_bytes: usize },
 }
 
 impl Default for ChannelSettings {
@@ -36,6 +115,8 @@ impl Default for ChannelSettings {
             allow_client_messages: true,
             persist_messages: false,
             rate_limit_per_minute: Some(60),
+            message_size_limit: None,
+            pause_policy: PausePolicy::default(),
         }
     }
 }
@@ -47,7 +128,77 @@ pub struct CreateChannelRequest {
     pub settings: Option<ChannelSettings>,
 }
 
+/// Cambios parciales de un canal; los campos ausentes no se modifican
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UpdateChannelRequest {
+    pub name: Option<String>,
+    /// Una cadena vacía elimina la descripción
+    pub description: Option<String>,
+    pub settings: Option<ChannelSettingsPatch>,
+}
+
+/// Cambios parciales de `ChannelSettings`. En los límites opcionales `0` elimina el límite.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChannelSettingsPatch {
+    pub max_connections: Option<usize>,
+    pub allow_client_messages: Option<bool>,
+    pub persist_messages: Option<bool>,
+    pub rate_limit_per_minute: Option<u32>,
+    pub message_size_limit: Option<usize>,
+    pub pause_policy: Option<PausePolicy>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeleteMode {
+    /// Elimina el canal de la base de datos
+    #[default]
+    Hard,
+    /// Marca el canal como `Archived` y lo conserva en la base de datos
+    Soft,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeleteChannelQuery {
+    pub mode: Option<DeleteMode>,
+    pub purge_messages: Option<bool>,
+    pub reason: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
-pub struct UpdateChannelStatusRequest {
-    pub status: ChannelStatus,
-}
\ No newline at end of file
+pub struct DeleteChannelResponse {
+    pub channel_id: Uuid,
+    pub mode: DeleteMode,
+    pub closed_connections: usize,
+    pub purged_messages: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopped_channel_cannot_restart() {
+        assert!(!ChannelStatus::Stopped.can_transition_to(ChannelStatus::Active));
+        assert!(!ChannelStatus::Stopped.can_transition_to(ChannelStatus::Paused));
+        assert!(ChannelStatus::Stopped.can_transition_to(ChannelStatus::Archived));
+    }
+
+    #[test]
+    fn test_restore_transitions() {
+        assert!(ChannelStatus::Stopped.can_transition_to(ChannelStatus::Created));
+        assert!(ChannelStatus::Archived.can_transition_to(ChannelStatus::Created));
+        assert!(!ChannelStatus::Archived.can_transition_to(ChannelStatus::Active));
+        assert!(!ChannelStatus::Active.can_transition_to(ChannelStatus::Created));
+    }
+
+    #[test]
+    fn test_lifecycle_transitions() {
+        assert!(ChannelStatus::Created.can_transition_to(ChannelStatus::Active));
+        assert!(ChannelStatus::Active.can_transition_to(ChannelStatus::Paused));
+        assert!(ChannelStatus::Paused.can_transition_to(ChannelStatus::Active));
+        assert!(!ChannelStatus::Created.can_transition_to(ChannelStatus::Paused));
+        assert!(!ChannelStatus::Active.can_transition_to(ChannelStatus::Active));
+        assert!(!ChannelStatus::Archived.can_transition_to(ChannelStatus::Archived));
+    }
+}
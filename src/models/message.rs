@@ -1,7 +1,16 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+/// Versión actual del sobre de mensajes
+pub const ENVELOPE_VERSION: u8 = 1;
+/// Cabeceras máximas por mensaje
+pub const MAX_MESSAGE_HEADERS: usize = 32;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BroadcastMessage {
     pub id: Uuid,
@@ -10,6 +19,258 @@ pub struct BroadcastMessage {
     pub message_type: MessageType,
     pub sender: MessageSender,
     pub timestamp: DateTime<Utc>,
+    /// Secuencia monótona por canal; `0` en mensajes guardados antes de existir
+    #[serde(default)]
+    pub seq: u64,
+    /// Tipo declarado del contenido binario; solo presente en mensajes binarios
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Contenido binario, en base64 en JSON. En redb se guarda aparte, en `MESSAGE_PAYLOADS_TABLE`
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "base64_payload")]
+    pub payload: Option<Vec<u8>>,
+    /// Contenido JSON estructurado; el sobre `v1` lo entrega tal cual
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// Cabeceras libres definidas por quien publica
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl BroadcastMessage {
+    /// Los mensajes binarios llevan siempre tipo de contenido
+    pub fn is_binary(&self) -> bool {
+        self.content_type.is_some()
+    }
+
+    /// Tamaño del contenido (binario, JSON o texto) más el de sus cabeceras
+    pub fn size(&self) -> usize {
+        let content = match (&self.payload, &self.data) {
+            (Some(payload), _) => payload.len(),
+            (None, Some(data)) => data.to_string().len(),
+            (None, None) => self.content.len(),
+        };
+        let headers: usize = self.headers.iter().map(|(name, value)| name.len() + value.len()).sum();
+
+        content + headers
+    }
+
+    /// Contenido como texto para el formato legacy: el JSON estructurado va serializado
+    pub fn legacy_text(&self) -> Cow<'_, str> {
+        match &self.data {
+            Some(data) => Cow::Owned(data.to_string()),
+            None => Cow::Borrowed(&self.content),
+        }
+    }
+
+    /// Copia del mensaje sin el contenido binario, tal como se guarda en `MESSAGES_TABLE`
+    pub fn without_payload(&self) -> BroadcastMessage {
+        BroadcastMessage {
+            id: self.id,
+            channel_id: self.channel_id,
+            content: self.content.clone(),
+            message_type: self.message_type.clone(),
+            sender: self.sender.clone(),
+            timestamp: self.timestamp,
+            seq: self.seq,
+            content_type: self.content_type.clone(),
+            payload: None,
+            data: self.data.clone(),
+            headers: self.headers.clone(),
+        }
+    }
+
+    /// Frame JSON con el que se entrega el mensaje, en vivo o al reanudar.
+    /// El contenido binario va en base64.
+    pub fn to_frame(&self, channel_name: &str, format: FrameFormat) -> serde_json::Result<String> {
+        match format {
+            FrameFormat::Legacy => serde_json::to_string(&self.frame_response(channel_name, true)),
+            FrameFormat::V1 => serde_json::to_string(&self.envelope(true)),
+        }
+    }
+
+    /// Cabecera JSON que precede al frame binario en WebSocket: el mismo frame sin el contenido
+    pub fn to_binary_header(&self, channel_name: &str, format: FrameFormat) -> serde_json::Result<String> {
+        match format {
+            FrameFormat::Legacy => serde_json::to_string(&self.frame_response(channel_name, false)),
+            FrameFormat::V1 => serde_json::to_string(&self.envelope(false)),
+        }
+    }
+
+    pub fn envelope(&self, inline_payload: bool) -> MessageEnvelope<'_> {
+        let (content_type, encoding, payload) = match (&self.payload, &self.data) {
+            (Some(bytes), _) => {
+                let content_type = self.content_type.as_deref().unwrap_or(DEFAULT_BINARY_CONTENT_TYPE);
+                if inline_payload {
+                    (content_type, PayloadEncoding::Base64, Some(EnvelopePayload::Base64(STANDARD.encode(bytes))))
+                } else {
+                    (content_type, PayloadEncoding::Binary, None)
+                }
+            }
+            (None, Some(data)) => ("application/json", PayloadEncoding::Json, Some(EnvelopePayload::Json(data))),
+            (None, None) => ("text/plain", PayloadEncoding::Text, Some(EnvelopePayload::Text(&self.content))),
+        };
+
+        MessageEnvelope {
+            v: ENVELOPE_VERSION,
+            kind: "message",
+            id: self.id,
+            channel_id: self.channel_id,
+            seq: self.seq,
+            timestamp: self.timestamp,
+            message_type: &self.message_type,
+            sender: &self.sender,
+            headers: &self.headers,
+            content_type,
+            encoding,
+            size: self.payload.as_ref().map(Vec::len),
+            payload,
+        }
+    }
+
+    fn frame_response(&self, channel_name: &str, inline_payload: bool) -> WebSocketResponse {
+        let mut response = match &self.sender {
+            MessageSender::Client(sender) => WebSocketResponse {
+                status: "client_message".to_string(),
+                message: format!("Client message in {}: {}", channel_name, self.legacy_text()),
+                channel_id: self.channel_id,
+                timestamp: self.timestamp,
+                seq: Some(self.seq),
+                data: Some(serde_json::json!({
+                    "original_message": self.legacy_text(),
+                    "sender": sender
+                })),
+            },
+            _ => WebSocketResponse {
+                status: "broadcast".to_string(),
+                message: self.legacy_text().into_owned(),
+                channel_id: self.channel_id,
+                timestamp: self.timestamp,
+                seq: Some(self.seq),
+                data: None,
+            },
+        };
+
+        if let (Some(content_type), Some(payload)) = (&self.content_type, &self.payload) {
+            response.message = format!("Binary message in {} ({}, {} bytes)", channel_name, content_type, payload.len());
+
+            let mut data = match response.data.take() {
+                Some(serde_json::Value::Object(data)) => data,
+                _ => serde_json::Map::new(),
+            };
+            data.remove("original_message");
+            data.insert("content_type".to_string(), content_type.as_str().into());
+            data.insert("size".to_string(), payload.len().into());
+
+            if inline_payload {
+                data.insert("encoding".to_string(), "base64".into());
+                data.insert("payload".to_string(), STANDARD.encode(payload).into());
+            } else {
+                data.insert("encoding".to_string(), "binary".into());
+            }
+            response.data = Some(serde_json::Value::Object(data));
+        }
+
+        if !self.headers.is_empty() {
+            let mut data = match response.data.take() {
+                Some(serde_json::Value::Object(data)) => data,
+                _ => serde_json::Map::new(),
+            };
+            data.insert("headers".to_string(), serde_json::json!(self.headers));
+            response.data = Some(serde_json::Value::Object(data));
+        }
+
+        response
+    }
+}
+
+/// Formato de los frames de mensajes que recibe un suscriptor. Los frames de control
+/// (bienvenida, estado, errores) son iguales en ambos.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameFormat {
+    /// `WebSocketResponse` con el contenido como texto en `message`
+    #[default]
+    Legacy,
+    /// `MessageEnvelope` con el contenido tal cual en `payload`
+    V1,
+}
+
+/// Sobre versionado de un mensaje publicado
+#[derive(Debug, Serialize)]
+pub struct MessageEnvelope<'a> {
+    pub v: u8,
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub message_type: &'a MessageType,
+    pub sender: &'a MessageSender,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: &'a BTreeMap<String, String>,
+    pub content_type: &'a str,
+    pub encoding: PayloadEncoding,
+    /// Bytes del contenido binario
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<usize>,
+    /// Ausente cuando el contenido sigue en un frame binario
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<EnvelopePayload<'a>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PayloadEncoding {
+    Json,
+    Text,
+    Base64,
+    /// El contenido llega en el siguiente frame binario de WebSocket
+    Binary,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum EnvelopePayload<'a> {
+    Json(&'a serde_json::Value),
+    Text(&'a str),
+    Base64(String),
+}
+
+/// Tipo asumido para frames binarios de clientes que no declaran uno
+pub const DEFAULT_BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Valida un tipo de contenido declarado (`tipo/subtipo`, parámetros opcionales)
+pub fn is_valid_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    let mut parts = essence.split('/');
+
+    content_type.len() <= 255
+        && !content_type.chars().any(|c| c.is_control())
+        && matches!(
+            (parts.next(), parts.next(), parts.next()),
+            (Some(kind), Some(subtype), None)
+                if !kind.is_empty() && !subtype.is_empty() && !essence.contains(char::is_whitespace)
+        )
+}
+
+mod base64_payload {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(payload: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match payload {
+            Some(bytes) => serializer.serialize_str(&STANDARD.encode(bytes)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|encoded| STANDARD.decode(encoded).map_err(serde::de::Error::custom))
+            .transpose()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,9 +288,25 @@ pub enum MessageSender {
     System,
 }
 
+/// Resultado de una publicación REST
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Delivery {
+    Delivered,
+    /// Retenido en el buffer de pausa hasta que el canal se reanude
+    Buffered,
+    /// Descartado por la política de pausa `drop`
+    Dropped,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BroadcastRequest {
-    pub content: String,
+    /// Contenido de texto; excluyente con `data`
+    pub content: Option<String>,
+    /// Contenido JSON estructurado, entregado tal cual en el sobre `v1`
+    pub data: Option<serde_json::Value>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
     pub message_type: Option<MessageType>,
 }
 
@@ -39,5 +316,95 @@ pub struct WebSocketResponse {
     pub message: String,
     pub channel_id: Uuid,
     pub timestamp: DateTime<Utc>,
+    /// Secuencia del mensaje en su canal; solo en frames de mensajes publicados
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
     pub data: Option<serde_json::Value>,
-}
\ No newline at end of file
+}
+
+/// Respuesta de long-polling: frames en el mismo formato que WebSocket y SSE
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PollBatch {
+    pub events: Vec<serde_json::Value>,
+    /// Secuencia a enviar como `cursor` en la siguiente petición
+    pub next_cursor: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct MessageHistoryQuery {
+    pub cursor: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageHistoryPage {
+    pub messages: Vec<BroadcastMessage>,
+    pub next_cursor: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary_message(payload: &[u8]) -> BroadcastMessage {
+        BroadcastMessage {
+            id: Uuid::new_v4(),
+            channel_id: Uuid::new_v4(),
+            content: String::new(),
+            message_type: MessageType::Broadcast,
+            sender: MessageSender::Server,
+            timestamp: Utc::now(),
+            seq: 3,
+            content_type: Some("image/png".to_string()),
+            payload: Some(payload.to_vec()),
+            data: None,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_binary_frames() {
+        let message = binary_message(&[0, 159, 255]);
+
+        let frame: serde_json::Value = serde_json::from_str(&message.to_frame("a", FrameFormat::Legacy).unwrap()).unwrap();
+        assert_eq!(frame["data"]["encoding"], "base64");
+        assert_eq!(frame["data"]["payload"], "AJ//");
+        assert_eq!(frame["data"]["size"], 3);
+
+        let header: serde_json::Value = serde_json::from_str(&message.to_binary_header("a", FrameFormat::Legacy).unwrap()).unwrap();
+        assert_eq!(header["data"]["encoding"], "binary");
+        assert!(header["data"].get("payload").is_none());
+
+        let stored: BroadcastMessage = serde_json::from_str(&serde_json::to_string(&message.without_payload()).unwrap()).unwrap();
+        assert!(stored.is_binary() && stored.payload.is_none());
+    }
+
+    #[test]
+    fn test_envelope_delivers_data_verbatim() {
+        let mut message = binary_message(&[]);
+        message.content_type = None;
+        message.payload = None;
+        message.data = Some(serde_json::json!({ "temperature": 21.5, "tags": ["a"] }));
+        message.headers.insert("source".to_string(), "sensor-1".to_string());
+
+        let envelope: serde_json::Value = serde_json::from_str(&message.to_frame("a", FrameFormat::V1).unwrap()).unwrap();
+        assert_eq!(envelope["v"], ENVELOPE_VERSION);
+        assert_eq!(envelope["encoding"], "json");
+        assert_eq!(envelope["payload"], serde_json::json!({ "temperature": 21.5, "tags": ["a"] }));
+        assert_eq!(envelope["headers"]["source"], "sensor-1");
+
+        let legacy: serde_json::Value = serde_json::from_str(&message.to_frame("a", FrameFormat::Legacy).unwrap()).unwrap();
+        assert_eq!(legacy["message"], r#"{"tags":["a"],"temperature":21.5}"#);
+    }
+
+    #[test]
+    fn test_content_type_validation() {
+        assert!(is_valid_content_type("application/x-protobuf"));
+        assert!(is_valid_content_type("text/plain; charset=utf-8"));
+        assert!(!is_valid_content_type("image"));
+        assert!(!is_valid_content_type("image/"));
+        assert!(!is_valid_content_type("a/b/c"));
+    }
+}
@@ -1,3 +1,5 @@
-
+pub mod admin;
+pub mod auth;
 pub mod channel;
-pub mod message;
\ No newline at end of file
+pub mod message;
+pub mod protocol;
\ No newline at end of file
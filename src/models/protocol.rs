@@ -0,0 +1,222 @@
+use crate::models::message::{FrameFormat, WebSocketResponse, ENVELOPE_VERSION};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Subprotocolo WebSocket con comandos tipados, negociado con `Sec-WebSocket-Protocol`
+pub const PROTOCOL_V1: &str = "emit-hub.v1";
+
+/// Frame de texto enviado por un cliente v1. `id` es libre (texto o número) y se
+/// devuelve tal cual en el resultado o error correspondiente.
+#[derive(Debug, Deserialize)]
+pub struct ClientFrame {
+    pub id: serde_json::Value,
+    #[serde(flatten)]
+    pub command: ClientCommand,
+}
+
+/// `channel_id` es obligatorio en el hub (`/ws`); en `/channels/{id}/ws` se puede omitir
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientCommand {
+    /// Empieza a recibir mensajes; con `since_seq` se reenvía antes lo perdido.
+    /// En el hub, con autenticación, `token` es el token de suscriptor del canal.
+    Subscribe {
+        channel_id: Option<Uuid>,
+        since_seq: Option<u64>,
+        token: Option<String>,
+    },
+    Unsubscribe {
+        channel_id: Option<Uuid>,
+    },
+    /// Publica `content` (texto) o `data` (JSON)
+    Publish {
+        channel_id: Option<Uuid>,
+        content: Option<String>,
+        data: Option<serde_json::Value>,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    /// Confirma la recepción hasta `seq`
+    Ack {
+        channel_id: Option<Uuid>,
+        seq: u64,
+    },
+    Ping,
+    /// Lista las conexiones suscritas al canal
+    Presence {
+        channel_id: Option<Uuid>,
+    },
+}
+
+impl ClientCommand {
+    pub fn channel_id(&self) -> Option<Uuid> {
+        match self {
+            ClientCommand::Subscribe { channel_id, .. }
+            | ClientCommand::Unsubscribe { channel_id }
+            | ClientCommand::Publish { channel_id, .. }
+            | ClientCommand::Ack { channel_id, .. }
+            | ClientCommand::Presence { channel_id } => *channel_id,
+            ClientCommand::Ping => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// El frame no es JSON válido o no corresponde a ningún comando
+    InvalidFrame,
+    /// Token de suscriptor ausente o inválido
+    Unauthorized,
+    Forbidden,
+    MessageTooLarge,
+    RateLimited,
+    /// El canal no admite mensajes de clientes
+    ClientMessagesDisabled,
+    /// El canal no existe, no está activo o no se sirve en esta conexión
+    ChannelUnavailable,
+    NotSubscribed,
+    ConnectionLimit,
+    Internal,
+}
+
+/// Error tipado de un comando
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Frame `error` del formato legacy: `data` lleva el código y los detalles
+    pub fn to_legacy(&self, channel_id: Uuid) -> WebSocketResponse {
+        let mut data = match &self.details {
+            Some(serde_json::Value::Object(details)) => details.clone(),
+            _ => serde_json::Map::new(),
+        };
+        data.insert("code".to_string(), serde_json::json!(self.code));
+
+        WebSocketResponse {
+            status: "error".to_string(),
+            message: self.message.clone(),
+            channel_id,
+            timestamp: Utc::now(),
+            seq: None,
+            data: Some(serde_json::Value::Object(data)),
+        }
+    }
+}
+
+/// Frame del servidor en el protocolo v1. Los mensajes publicados usan `MessageEnvelope`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerFrame {
+    /// Respuesta correcta a un comando
+    Result {
+        id: serde_json::Value,
+        result: serde_json::Value,
+    },
+    /// `id` es nulo si el error no corresponde a un comando identificable
+    Error {
+        id: Option<serde_json::Value>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel_id: Option<Uuid>,
+        error: CommandError,
+    },
+    /// Aviso del servidor: conexión, cambios de estado del canal, huecos en el reenvío.
+    /// Solo los avisos del hub que no afectan a un canal van sin `channel_id`.
+    Event {
+        event: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        channel_id: Option<Uuid>,
+        timestamp: DateTime<Utc>,
+        message: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        data: Option<serde_json::Value>,
+    },
+}
+
+#[derive(Serialize)]
+struct Versioned<'a> {
+    v: u8,
+    #[serde(flatten)]
+    frame: &'a ServerFrame,
+}
+
+impl ServerFrame {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(&Versioned { v: ENVELOPE_VERSION, frame: self })
+    }
+}
+
+impl WebSocketResponse {
+    /// Frame de control en el formato del suscriptor; en v1 el `status` pasa a ser el `event`
+    pub fn to_frame(&self, format: FrameFormat) -> serde_json::Result<String> {
+        match format {
+            FrameFormat::Legacy => serde_json::to_string(self),
+            FrameFormat::V1 => ServerFrame::Event {
+                event: self.status.clone(),
+                channel_id: Some(self.channel_id),
+                timestamp: self.timestamp,
+                message: self.message.clone(),
+                data: self.data.clone(),
+            }
+            .to_json(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_client_frames() {
+        let frame: ClientFrame =
+            serde_json::from_str(r#"{"id":7,"type":"publish","data":{"a":1},"headers":{"k":"v"}}"#).unwrap();
+        assert_eq!(frame.id, serde_json::json!(7));
+        assert!(matches!(frame.command, ClientCommand::Publish { data: Some(_), content: None, .. }));
+
+        let frame: ClientFrame = serde_json::from_str(r#"{"id":"a","type":"ping"}"#).unwrap();
+        assert!(matches!(frame.command, ClientCommand::Ping));
+
+        assert!(serde_json::from_str::<ClientFrame>(r#"{"id":1,"type":"shout"}"#).is_err());
+        assert!(serde_json::from_str::<ClientFrame>(r#"{"type":"ping"}"#).is_err());
+    }
+
+    #[test]
+    fn test_error_frames() {
+        let error = CommandError::new(ErrorCode::RateLimited, "Rate limit exceeded")
+            .with_details(serde_json::json!({ "retry_after_ms": 10 }));
+
+        let frame: serde_json::Value = serde_json::from_str(
+            &ServerFrame::Error { id: Some(serde_json::json!("x")), channel_id: None, error: error.clone() }
+                .to_json()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(frame["v"], ENVELOPE_VERSION);
+        assert_eq!(frame["type"], "error");
+        assert_eq!(frame["error"]["code"], "rate_limited");
+
+        let legacy = error.to_legacy(Uuid::nil()).data.unwrap();
+        assert_eq!(legacy, serde_json::json!({ "code": "rate_limited", "retry_after_ms": 10 }));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::error::AppError;
+use crate::models::auth::{ApiKey, IssuedApiKey, Scope};
+use crate::state::AppState;
+use anyhow::Result;
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "ehk_";
+
+pub struct AuthService;
+
+impl AuthService {
+    /// Genera una nueva API key. El secreto solo se devuelve aquí.
+    pub async fn issue_key(state: &AppState, name: String, scopes: Vec<Scope>) -> Result<IssuedApiKey> {
+        if name.trim().is_empty() {
+            anyhow::bail!("API key name cannot be empty");
+        }
+        if scopes.is_empty() {
+            anyhow::bail!("API key needs at least one scope");
+        }
+
+        let secret = format!("{}{}{}", KEY_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            name,
+            prefix: secret[..KEY_PREFIX.len() + 8].to_string(),
+            scopes,
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+
+        state.save_api_key(&Self::hash_key(&secret), &api_key).await?;
+
+        tracing::info!("Issued API key {} ({}) with scopes {:?}", api_key.name, api_key.id, api_key.scopes);
+        Ok(IssuedApiKey { key: secret, api_key })
+    }
+
+    pub async fn revoke_key(state: &AppState, key_id: Uuid) -> Result<ApiKey> {
+        let (hash, mut api_key) = state
+            .find_api_key_by_id(&key_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("API key not found"))?;
+
+        if api_key.revoked_at.is_none() {
+            api_key.revoked_at = Some(Utc::now());
+            state.save_api_key(&hash, &api_key).await?;
+            tracing::info!("Revoked API key {} ({})", api_key.name, api_key.id);
+        }
+
+        Ok(api_key)
+    }
+
+    pub async fn list_keys(state: &AppState) -> Result<Vec<ApiKey>> {
+        state.list_api_keys().await
+    }
+
+    /// Valida el secreto presentado y comprueba que tenga `scope`
+    pub async fn authorize(state: &AppState, secret: Option<&str>, scope: Scope) -> Result<ApiKey> {
+        let secret = secret.ok_or(AppError::Unauthorized)?;
+
+        let api_key = state
+            .get_api_key(&Self::hash_key(secret))
+            .await?
+            .filter(|api_key| api_key.revoked_at.is_none())
+            .ok_or(AppError::Unauthorized)?;
+
+        if !api_key.has_scope(scope) {
+            return Err(AppError::Forbidden { scope }.into());
+        }
+
+        Ok(api_key)
+    }
+
+    fn hash_key(secret: &str) -> String {
+        Sha256::digest(secret.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_support::{test_state, TempDir};
+
+    fn rejection(result: Result<ApiKey>) -> AppError {
+        match result.unwrap_err().downcast::<AppError>() {
+            Ok(error) => error,
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authorize() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let issued = AuthService::issue_key(&state, "reader".to_string(), vec![Scope::ChannelsRead])
+            .await
+            .unwrap();
+
+        let api_key = AuthService::authorize(&state, Some(&issued.key), Scope::ChannelsRead).await.unwrap();
+        assert_eq!(api_key.id, issued.api_key.id);
+
+        let missing_scope = AuthService::authorize(&state, Some(&issued.key), Scope::ChannelsWrite).await;
+        assert!(matches!(rejection(missing_scope), AppError::Forbidden { scope: Scope::ChannelsWrite }));
+
+        let unknown = AuthService::authorize(&state, Some("ehk_unknown"), Scope::ChannelsRead).await;
+        assert!(matches!(rejection(unknown), AppError::Unauthorized));
+        assert!(matches!(rejection(AuthService::authorize(&state, None, Scope::ChannelsRead).await), AppError::Unauthorized));
+
+        AuthService::revoke_key(&state, issued.api_key.id).await.unwrap();
+        let revoked = AuthService::authorize(&state, Some(&issued.key), Scope::ChannelsRead).await;
+        assert!(matches!(rejection(revoked), AppError::Unauthorized));
+    }
+}
@@ -0,0 +1,250 @@
+use crate::config::Config;
+use crate::models::admin::BackupInfo;
+use crate::state::AppState;
+use anyhow::Result;
+use chrono::{NaiveDateTime, SubsecRound, Utc};
+use redb::Database;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const BACKUP_MARKER: &str = "-backup-";
+const BACKUP_EXTENSION: &str = "redb";
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+pub struct BackupService;
+
+impl BackupService {
+    /// Lanza los backups periódicos si `persistence.auto_backup` está activo
+    pub fn spawn(state: Arc<AppState>) {
+        let persistence = &state.config.persistence;
+        if !persistence.auto_backup {
+            return;
+        }
+
+        let interval_hours = persistence.backup_interval_hours as u64;
+
+        actix_web::rt::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_hours * 3600));
+            // El primer tick es inmediato; no hacemos backup justo al arrancar
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                if let Err(e) = Self::create_backup(&state).await {
+                    tracing::error!("Automatic backup failed: {}", e);
+                }
+            }
+        });
+
+        tracing::info!("Automatic backups scheduled every {} hours", interval_hours);
+    }
+
+    /// Crea una copia consistente de la base de datos y aplica la rotación keep-N.
+    ///
+    /// Mientras se copia el archivo se mantiene abierta una transacción de escritura,
+    /// lo que bloquea a otros escritores: todo lo confirmado ya está en disco y nada
+    /// nuevo puede escribirse hasta terminar la copia.
+    pub async fn create_backup(state: &AppState) -> Result<BackupInfo> {
+        let db = state.db.clone();
+        let db_path = PathBuf::from(&state.config.db_path);
+        let keep = state.config.persistence.backup_keep;
+
+        let info = tokio::task::spawn_blocking(move || -> Result<BackupInfo> {
+            let created_at = Utc::now().trunc_subsecs(3);
+            let name = Self::backup_name(&db_path, &created_at.naive_utc());
+            let backup_path = Self::backup_dir(&db_path).join(&name);
+
+            let write_txn = db.begin_write()?;
+            let copy_result = std::fs::copy(&db_path, &backup_path);
+            write_txn.abort()?;
+            let size_bytes = copy_result?;
+
+            // Abrir la copia valida que es una base de datos utilizable
+            if let Err(e) = Database::open(&backup_path) {
+                let _ = std::fs::remove_file(&backup_path);
+                anyhow::bail!("Backup {} failed verification: {}", name, e);
+            }
+
+            Self::rotate(&db_path, keep)?;
+
+            Ok(BackupInfo {
+                name,
+                size_bytes,
+                created_at,
+            })
+        })
+        .await??;
+
+        tracing::info!("Created database backup {} ({} bytes)", info.name, info.size_bytes);
+        Ok(info)
+    }
+
+    /// Lista los backups de `db_path`, del más reciente al más antiguo
+    pub fn list_backups(db_path: &str) -> Result<Vec<BackupInfo>> {
+        let db_path = Path::new(db_path);
+        let dir = Self::backup_dir(db_path);
+        let prefix = Self::backup_prefix(db_path);
+
+        let mut backups = Vec::new();
+        if !dir.exists() {
+            return Ok(backups);
+        }
+
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            let Some(timestamp) = name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(&format!(".{}", BACKUP_EXTENSION)))
+            else {
+                continue;
+            };
+
+            let Ok(created_at) = NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT) else {
+                continue;
+            };
+
+            backups.push(BackupInfo {
+                name,
+                size_bytes: entry.metadata()?.len(),
+                created_at: created_at.and_utc(),
+            });
+        }
+
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+        Ok(backups)
+    }
+
+    /// Restaura un backup sobre `db_path` antes de abrir la base de datos.
+    /// La base de datos actual se conserva con el sufijo `.pre-restore-<timestamp>`.
+    pub fn restore_backup(config: &Config, name: &str) -> Result<()> {
+        let db_path = Path::new(&config.db_path);
+
+        let backup = Self::list_backups(&config.db_path)?
+            .into_iter()
+            .find(|backup| backup.name == name)
+            .ok_or_else(|| anyhow::anyhow!("Backup '{}' not found next to {}", name, config.db_path))?;
+
+        let backup_path = Self::backup_dir(db_path).join(&backup.name);
+        Database::open(&backup_path)
+            .map_err(|e| anyhow::anyhow!("Backup '{}' is not a valid database: {}", name, e))?;
+
+        if db_path.exists() {
+            let previous = format!(
+                "{}.pre-restore-{}",
+                config.db_path,
+                Utc::now().format(BACKUP_TIMESTAMP_FORMAT)
+            );
+            std::fs::rename(db_path, &previous)?;
+            tracing::warn!("Moved current database to {}", previous);
+        }
+
+        std::fs::copy(&backup_path, db_path)?;
+        tracing::info!("Restored database from backup {}", backup.name);
+        Ok(())
+    }
+
+    fn rotate(db_path: &Path, keep: usize) -> Result<()> {
+        let dir = Self::backup_dir(db_path);
+        let backups = Self::list_backups(&db_path.to_string_lossy())?;
+
+        for backup in backups.iter().skip(keep) {
+            std::fs::remove_file(dir.join(&backup.name))?;
+            tracing::info!("Removed old backup {}", backup.name);
+        }
+
+        Ok(())
+    }
+
+    fn backup_dir(db_path: &Path) -> PathBuf {
+        match db_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        }
+    }
+
+    fn backup_prefix(db_path: &Path) -> String {
+        let stem = db_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| "emit_hub".to_string());
+        format!("{}{}", stem, BACKUP_MARKER)
+    }
+
+    fn backup_name(db_path: &Path, created_at: &NaiveDateTime) -> String {
+        format!(
+            "{}{}.{}",
+            Self::backup_prefix(db_path),
+            created_at.format(BACKUP_TIMESTAMP_FORMAT),
+            BACKUP_EXTENSION
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::channel::{ChannelSettings, ChannelStatus};
+    use crate::utils::test_support::{test_channel, test_config, TempDir};
+
+    #[tokio::test]
+    async fn test_backup_rotation_and_restore_round_trip() {
+        let dir = TempDir::new();
+        let mut config = test_config(&dir);
+        config.persistence.backup_keep = 2;
+
+        let state = AppState::new(&config).await.unwrap();
+        let kept = test_channel(ChannelStatus::Created, ChannelSettings::default());
+        state.save_channel(&kept).await.unwrap();
+
+        let mut created = Vec::new();
+        for _ in 0..3 {
+            created.push(BackupService::create_backup(&state).await.unwrap().name);
+            // Los nombres tienen resolución de milisegundos
+            tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        }
+
+        // Rotación keep-N: quedan los dos más recientes, del más nuevo al más antiguo
+        let names: Vec<String> = BackupService::list_backups(&config.db_path)
+            .unwrap()
+            .into_iter()
+            .map(|backup| backup.name)
+            .collect();
+        assert_eq!(names, vec![created[2].clone(), created[1].clone()]);
+
+        let lost = test_channel(ChannelStatus::Created, ChannelSettings::default());
+        state.save_channel(&lost).await.unwrap();
+        drop(state);
+
+        BackupService::restore_backup(&config, &created[2]).unwrap();
+
+        // La base de datos anterior se conserva junto a la restaurada
+        let preserved = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().contains(".pre-restore-"));
+        assert!(preserved);
+
+        let state = AppState::new(&config).await.unwrap();
+        assert!(state.load_channel(&kept.id).await.unwrap().is_some());
+        assert!(state.load_channel(&lost.id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_restore_rejects_invalid_backup() {
+        let dir = TempDir::new();
+        let config = test_config(&dir);
+        drop(AppState::new(&config).await.unwrap());
+
+        let name = BackupService::backup_name(Path::new(&config.db_path), &Utc::now().naive_utc().trunc_subsecs(3));
+        std::fs::write(dir.path().join(&name), b"not a database").unwrap();
+
+        assert!(BackupService::restore_backup(&config, &name).is_err());
+        assert!(BackupService::restore_backup(&config, "missing.redb").is_err());
+        // La base de datos actual no se toca
+        assert!(Path::new(&config.db_path).exists());
+        assert!(Database::open(&config.db_path).is_ok());
+    }
+}
@@ -1,4 +1,10 @@
-use crate::models::channel::{Channel, ChannelStatus, CreateChannelRequest};
+use crate::models::channel::{
+    BufferDepth, Channel, ChannelStatus, ChannelTransition, CreateChannelRequest,
+    DeleteChannelResponse, DeleteMode, UpdateChannelRequest,
+};
+use crate::services::message_service::MessageService;
+use crate::error::AppError;
+use crate::utils::rate_limit::RateLimitKey;
 use crate::state::AppState;
 use anyhow::Result;
 use uuid::Uuid;
@@ -19,6 +25,7 @@ impl ChannelService {
             created_at: Utc::now(),
             updated_at: Utc::now(),
             settings: request.settings.unwrap_or_default(),
+            buffered: BufferDepth::default(),
         };
 
         state.save_channel(&channel).await?;
@@ -28,54 +35,545 @@ impl ChannelService {
         Ok(channel)
     }
 
-    pub async fn start_channel(state: &AppState, channel_id: Uuid) -> Result<Channel> {
-        let mut active_channels = state.active_channels.write().await;
+    pub async fn start_channel(
+        state: &AppState,
+        channel_id: Uuid,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<Channel> {
+        // El turno de publicación se toma antes de reanudar y se mantiene hasta vaciar el
+        // buffer de pausa, para que las publicaciones nuevas no adelanten a las retenidas
+        let order = state.publish_order(&channel_id).await;
 
-        if let Some(channel) = active_channels.get_mut(&channel_id) {
-            channel.status = ChannelStatus::Active;
-            channel.updated_at = Utc::now();
-            state.save_channel(channel).await?;
+        // Estado y buffer cambian juntos bajo el lock de canales: ningún mensaje entra
+        // en el buffer después de tomarlo
+        let (channel, transition, buffered) = {
+            let mut active_channels = state.active_channels.write().await;
+            let Some(channel) = active_channels.get_mut(&channel_id) else {
+                return Err(state.unloaded_transition_error(&channel_id, ChannelStatus::Active).await);
+            };
 
-            tracing::info!("Started channel: {} ({})", channel.name, channel.id);
-            Ok(channel.clone())
-        } else {
-            anyhow::bail!("Channel not found")
+            let transition =
+                AppState::apply_transition(channel, ChannelStatus::Active, actor, reason)?;
+            state.save_channel_transition(channel, &transition).await?;
+            (channel.clone(), transition, state.take_pause_buffer(&channel_id).await)
+        };
+
+        Self::notify_transition(state, &channel, &transition).await;
+
+        if !buffered.is_empty() {
+            MessageService::flush_pause_buffer(state, &order, &channel, buffered).await?;
         }
+        drop(order);
+
+        tracing::info!("Started channel: {} ({}) by {}", channel.name, channel.id, actor);
+        Ok(channel)
     }
 
-    pub async fn pause_channel(state: &AppState, channel_id: Uuid) -> Result<Channel> {
-        state.update_channel_status(channel_id, ChannelStatus::Paused).await?;
+    pub async fn pause_channel(
+        state: &AppState,
+        channel_id: Uuid,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<Channel> {
+        let (channel, transition) = state
+            .transition_channel(channel_id, ChannelStatus::Paused, actor, reason)
+            .await?;
+        Self::notify_transition(state, &channel, &transition).await;
 
-        if let Some(channel) = state.get_channel(&channel_id).await {
-            tracing::info!("Paused channel: {} ({})", channel.name, channel.id);
-            Ok(channel)
-        } else {
-            anyhow::bail!("Channel not found")
-        }
+        tracing::info!("Paused channel: {} ({}) by {}", channel.name, channel.id, actor);
+        Ok(channel)
     }
 
-    pub async fn stop_channel(state: &AppState, channel_id: Uuid) -> Result<Channel> {
-        state.update_channel_status(channel_id, ChannelStatus::Stopped).await?;
+    pub async fn stop_channel(
+        state: &AppState,
+        channel_id: Uuid,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<Channel> {
+        // Con el turno de publicación ningún mensaje se numera a medias de la parada
+        let order = state.publish_order(&channel_id).await;
+        let (channel, transition) = state
+            .transition_channel(channel_id, ChannelStatus::Stopped, actor, reason)
+            .await?;
+        Self::notify_transition(state, &channel, &transition).await;
+
+        // Cerrar todas las conexiones del canal una vez avisadas
+        state.close_channel_connections(&channel_id, "channel stopped").await;
+        Self::discard_pause_buffer(state, &channel_id).await;
+        drop(order);
 
-        // Cerrar todas las conexiones del canal
+        tracing::info!("Stopped channel: {} ({}) by {}", channel.name, channel.id, actor);
+        Ok(channel)
+    }
+
+    /// Historial de transiciones de un canal, también de canales archivados
+    pub async fn channel_history(state: &AppState, channel_id: Uuid) -> Result<Vec<ChannelTransition>> {
+        // Un canal borrado del todo conserva su historial, que termina en `Deleted`
+        let history = state.get_channel_history(&channel_id).await?;
+        if history.is_empty()
+            && state.get_channel(&channel_id).await.is_none()
+            && state.load_channel(&channel_id).await?.is_none()
         {
-            let mut connections = state.connections.lock().await;
-            if let Some(sessions) = connections.remove(&channel_id) {
-                for session in sessions {
-                    let _ = session.close(None).await;
+            return Err(AppError::ChannelNotFound.into());
+        }
+
+        Ok(history)
+    }
+
+    /// Canales en memoria más los que solo están en redb (detenidos o archivados).
+    /// Sin `status` se omiten los archivados.
+    pub async fn list_channels(
+        state: &AppState,
+        status: Option<ChannelStatus>,
+    ) -> Result<Vec<Channel>> {
+        let mut channels: Vec<Channel> = state.active_channels.read().await.values().cloned().collect();
+
+        for channel in state.load_stored_channels().await? {
+            if !channels.iter().any(|loaded| loaded.id == channel.id) {
+                channels.push(channel);
+            }
+        }
+
+        channels.retain(|channel| match status {
+            Some(status) => channel.status == status,
+            None => channel.status != ChannelStatus::Archived,
+        });
+        channels.sort_by_key(|channel| channel.created_at);
+
+        Ok(channels)
+    }
+
+    /// Busca el canal en memoria y, si no está, en redb. Los archivados no se exponen.
+    pub async fn get_channel(state: &AppState, channel_id: Uuid) -> Result<Channel> {
+        let channel = match state.get_channel(&channel_id).await {
+            Some(channel) => Some(channel),
+            None => state.load_channel(&channel_id).await?,
+        };
+
+        channel
+            .filter(|channel| channel.status != ChannelStatus::Archived)
+            .ok_or_else(|| AppError::ChannelNotFound.into())
+    }
+
+    /// Devuelve un canal detenido o archivado al registro en memoria con estado `Created`
+    pub async fn restore_channel(
+        state: &AppState,
+        channel_id: Uuid,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<Channel> {
+        let mut active_channels = state.active_channels.write().await;
+
+        let mut channel = match active_channels.get(&channel_id) {
+            Some(channel) => channel.clone(),
+            None => state
+                .load_channel(&channel_id)
+                .await?
+                .ok_or(AppError::ChannelNotFound)?,
+        };
+
+        let transition =
+            AppState::apply_transition(&mut channel, ChannelStatus::Created, actor, reason)?;
+        state.save_channel_transition(&channel, &transition).await?;
+        active_channels.insert(channel_id, channel.clone());
+
+        tracing::info!(
+            "Restored channel: {} ({}) from {:?} by {}",
+            channel.name,
+            channel.id,
+            transition.from,
+            actor
+        );
+        Ok(channel)
+    }
+
+    /// Elimina un canal: lo retira de redb, después de memoria, y cierra sus sesiones.
+    /// En modo `Soft` se conserva archivado y `purge_messages` decide si se borra su historial;
+    /// el modo `Hard` siempre borra los mensajes para no dejar filas huérfanas.
+    pub async fn delete_channel(
+        state: &AppState,
+        channel_id: Uuid,
+        mode: DeleteMode,
+        purge_messages: bool,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<DeleteChannelResponse> {
+        if mode == DeleteMode::Hard && !purge_messages {
+            anyhow::bail!("Hard delete always purges messages; use mode=soft to keep them");
+        }
+
+        // El turno de publicación se mantiene hasta olvidar el canal: una publicación en
+        // curso termina antes y las que esperan encuentran el canal ya borrado
+        let order = state.publish_order(&channel_id).await;
+        let mut active_channels = state.active_channels.write().await;
+
+        let mut channel = match active_channels.get(&channel_id) {
+            Some(channel) => channel.clone(),
+            None => state
+                .load_channel(&channel_id)
+                .await?
+                .ok_or(AppError::ChannelNotFound)?,
+        };
+
+        // El borrado lógico es una transición a `Archived` y el definitivo a `Deleted`,
+        // que queda como último registro del historial. Se validan antes de tocar nada.
+        let status = match mode {
+            DeleteMode::Soft => ChannelStatus::Archived,
+            DeleteMode::Hard => ChannelStatus::Deleted,
+        };
+        let transition = AppState::apply_transition(&mut channel, status, actor, reason)?;
+
+        // Primero redb: si falla, el canal sigue intacto en memoria
+        let purged_messages = state
+            .delete_channel_record(&channel, &transition, purge_messages)
+            .await?;
+        active_channels.remove(&channel_id);
+        drop(active_channels);
+
+        match mode {
+            DeleteMode::Soft => Self::notify_transition(state, &channel, &transition).await,
+            DeleteMode::Hard => {
+                Self::notify(state, &channel, "deleted", serde_json::json!({ "mode": mode })).await
+            }
+        }
+
+        let closed_connections = state.close_channel_connections(&channel_id, "channel deleted").await;
+        Self::discard_pause_buffer(state, &channel_id).await;
+        state.drop_replay_log(&channel_id).await;
+        drop(order);
+        state.rate_limiter.remove(&RateLimitKey::Channel(channel_id));
+
+        tracing::info!(
+            "Deleted channel: {} ({}) mode={:?} by {}, closed {} connections, purged {} messages",
+            channel.name,
+            channel_id,
+            mode,
+            actor,
+            closed_connections,
+            purged_messages
+        );
+
+        Ok(DeleteChannelResponse {
+            channel_id,
+            mode,
+            closed_connections,
+            purged_messages,
+        })
+    }
+
+    /// Aplica cambios de nombre, descripción y settings en caliente.
+    /// Las sesiones conectadas reciben un `StatusUpdate` con los campos modificados.
+    pub async fn update_channel(
+        state: &AppState,
+        channel_id: Uuid,
+        request: UpdateChannelRequest,
+    ) -> Result<Channel> {
+        // Se valida todo antes de tocar el canal: una petición rechazada no deja cambios a medias
+        if request.name.as_ref().is_some_and(|name| name.trim().is_empty()) {
+            anyhow::bail!("Channel name cannot be empty");
+        }
+        if request.settings.as_ref().and_then(|patch| patch.max_connections) == Some(0) {
+            anyhow::bail!("max_connections must be greater than 0");
+        }
+
+        let mut changes = Vec::new();
+
+        let channel = {
+            let mut active_channels = state.active_channels.write().await;
+            let current = active_channels
+                .get_mut(&channel_id)
+                .ok_or(AppError::ChannelNotFound)?;
+            let mut channel = current.clone();
+
+            if let Some(name) = request.name {
+                channel.name = name;
+                changes.push("name");
+            }
+
+            if let Some(description) = request.description {
+                channel.description = Some(description).filter(|d| !d.is_empty());
+                changes.push("description");
+            }
+
+            if let Some(patch) = request.settings {
+                let settings = &mut channel.settings;
+
+                if let Some(max_connections) = patch.max_connections {
+                    settings.max_connections = max_connections;
+                    changes.push("settings.max_connections");
+                }
+                if let Some(allow) = patch.allow_client_messages {
+                    settings.allow_client_messages = allow;
+                    changes.push("settings.allow_client_messages");
+                }
+                if let Some(persist) = patch.persist_messages {
+                    settings.persist_messages = persist;
+                    changes.push("settings.persist_messages");
+                }
+                if let Some(rate_limit) = patch.rate_limit_per_minute {
+                    settings.rate_limit_per_minute = Some(rate_limit).filter(|limit| *limit > 0);
+                    changes.push("settings.rate_limit_per_minute");
+                }
+                if let Some(size_limit) = patch.message_size_limit {
+                    settings.message_size_limit = Some(size_limit).filter(|limit| *limit > 0);
+                    changes.push("settings.message_size_limit");
                 }
+                if let Some(pause_policy) = patch.pause_policy {
+                    settings.pause_policy = pause_policy;
+                    changes.push("settings.pause_policy");
+                }
+            }
+
+            if changes.is_empty() {
+                return Ok(channel);
             }
+
+            // Primero redb: si falla, el canal en memoria queda como estaba
+            channel.updated_at = Utc::now();
+            state.save_channel(&channel).await?;
+            *current = channel.clone();
+            channel
+        };
+
+        Self::notify(
+            state,
+            &channel,
+            "updated",
+            serde_json::json!({ "changes": changes, "channel": channel }),
+        )
+        .await;
+
+        // Reducir max_connections desconecta las sesiones sobrantes
+        let closed = state
+            .enforce_connection_limit(&channel_id, state.max_connections_for(&channel))
+            .await;
+
+        tracing::info!(
+            "Updated channel: {} ({}) fields {:?}, closed {} connections",
+            channel.name,
+            channel.id,
+            changes,
+            closed
+        );
+        Ok(channel)
+    }
+
+    /// Nombre del evento de ciclo de vida que ven los clientes para una transición
+    fn lifecycle_event(transition: &ChannelTransition) -> &'static str {
+        match (transition.from, transition.to) {
+            (ChannelStatus::Paused, ChannelStatus::Active) => "resumed",
+            (_, ChannelStatus::Active) => "started",
+            (_, ChannelStatus::Paused) => "paused",
+            (_, ChannelStatus::Stopped) => "stopped",
+            (_, ChannelStatus::Archived) => "archived",
+            (_, ChannelStatus::Created) => "restored",
+            (_, ChannelStatus::Deleted) => "deleted",
         }
+    }
+
+    async fn notify_transition(state: &AppState, channel: &Channel, transition: &ChannelTransition) {
+        let event = Self::lifecycle_event(transition);
+        Self::notify(state, channel, event, serde_json::json!({ "transition": transition })).await;
+    }
+
+    /// Envía el `StatusUpdate` a las sesiones del canal; un fallo de envío no aborta la operación
+    async fn notify(state: &AppState, channel: &Channel, event: &str, details: serde_json::Value) {
+        if let Err(e) = MessageService::send_status_update(state, channel, event, details).await {
+            tracing::warn!("Failed to send {} status update for channel {}: {}", event, channel.id, e);
+        }
+    }
 
-        if let Some(channel) = state.get_channel(&channel_id).await {
-            tracing::info!("Stopped channel: {} ({})", channel.name, channel.id);
-            Ok(channel)
-        } else {
-            anyhow::bail!("Channel not found")
+    async fn discard_pause_buffer(state: &AppState, channel_id: &Uuid) {
+        let discarded = state.take_pause_buffer(channel_id).await.len();
+        if discarded > 0 {
+            tracing::warn!("Discarded {} buffered messages of channel {}", discarded, channel_id);
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::channel::ChannelSettingsPatch;
+    use crate::models::message::MessageHistoryQuery;
+    use crate::state::{Connection, PolledFrame, Sink};
+    use crate::utils::test_support::{loaded_channel, test_state, text_message, TempDir};
+    use chrono::{DateTime, Duration};
+    use tokio::sync::mpsc;
+
+    /// Canal activo en memoria y en redb con `count` mensajes guardados
+    async fn channel_with_messages(state: &AppState, count: usize) -> Channel {
+        let channel = loaded_channel(state, ChannelStatus::Active, Default::default()).await;
+        for i in 0..count {
+            let message = text_message(channel.id, &format!("m{}", i), Utc::now());
+            state.save_message(&message).await.unwrap();
+        }
+        channel
+    }
+
+    async fn stored_messages(state: &AppState, channel_id: &Uuid) -> usize {
+        let query = MessageHistoryQuery::default();
+        state.get_channel_messages(channel_id, &query).await.unwrap().messages.len()
+    }
+
+    #[tokio::test]
+    async fn test_hard_delete_purges_messages() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = channel_with_messages(&state, 3).await;
+
+        let response = ChannelService::delete_channel(&state, channel.id, DeleteMode::Hard, true, "admin", None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.purged_messages, 3);
+        assert!(state.get_channel(&channel.id).await.is_none());
+        assert!(state.load_channel(&channel.id).await.unwrap().is_none());
+        assert_eq!(stored_messages(&state, &channel.id).await, 0);
+
+        // El historial sobrevive al borrado y termina con el registro `Deleted`
+        let history = ChannelService::channel_history(&state, channel.id).await.unwrap();
+        let last = history.last().unwrap();
+        assert_eq!((last.from, last.to), (ChannelStatus::Active, ChannelStatus::Deleted));
+        assert_eq!(last.actor, "admin");
+    }
+
+    #[tokio::test]
+    async fn test_hard_delete_without_purge_is_rejected() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = channel_with_messages(&state, 2).await;
+
+        let result = ChannelService::delete_channel(&state, channel.id, DeleteMode::Hard, false, "admin", None).await;
+
+        assert!(result.is_err());
+        assert!(state.get_channel(&channel.id).await.is_some());
+        assert!(state.load_channel(&channel.id).await.unwrap().is_some());
+        assert_eq!(stored_messages(&state, &channel.id).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_archives_and_keeps_messages() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = channel_with_messages(&state, 2).await;
+
+        let response = ChannelService::delete_channel(&state, channel.id, DeleteMode::Soft, false, "admin", None)
+            .await
+            .unwrap();
+
+        assert_eq!(response.purged_messages, 0);
+        assert!(state.get_channel(&channel.id).await.is_none());
+        let stored = state.load_channel(&channel.id).await.unwrap().unwrap();
+        assert_eq!(stored.status, ChannelStatus::Archived);
+        assert_eq!(stored_messages(&state, &channel.id).await, 2);
+
+        // Un canal archivado no admite otro borrado lógico y queda como estaba
+        let again = ChannelService::delete_channel(&state, channel.id, DeleteMode::Soft, true, "admin", None).await;
+        assert!(again.is_err());
+        assert_eq!(stored_messages(&state, &channel.id).await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_publish_after_delete_leaves_no_rows() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let stopped = channel_with_messages(&state, 0).await;
+        let deleted = channel_with_messages(&state, 0).await;
+        state.publish(&deleted, text_message(deleted.id, "before", Utc::now())).await.unwrap();
+
+        ChannelService::stop_channel(&state, stopped.id, "admin", None).await.unwrap();
+        ChannelService::delete_channel(&state, deleted.id, DeleteMode::Hard, true, "admin", None)
+            .await
+            .unwrap();
+
+        // Una publicación que llega tarde con la copia del canal ya no numera ni guarda nada
+        let late = state.publish(&stopped, text_message(stopped.id, "late", Utc::now())).await;
+        assert!(late.is_err());
+        let late = state.publish(&deleted, text_message(deleted.id, "late", Utc::now())).await.unwrap_err();
+        assert!(matches!(late.downcast_ref::<AppError>(), Some(AppError::ChannelNotFound)));
+
+        assert_eq!(stored_messages(&state, &deleted.id).await, 0);
+        assert!(!state.replay_logs.lock().await.contains_key(&deleted.id));
+        let read_txn = state.db.begin_read().unwrap();
+        let counters = read_txn.open_table(crate::state::CHANNEL_SEQ_COUNTERS_TABLE).unwrap();
+        assert!(counters.get(deleted.id.to_string().as_str()).unwrap().is_none());
+    }
+
+    fn update(name: Option<&str>, settings: Option<ChannelSettingsPatch>) -> UpdateChannelRequest {
+        UpdateChannelRequest { name: name.map(str::to_string), description: None, settings }
+    }
+
+    /// Suscriptor de long-polling conectado en `connected_at`
+    async fn subscribe(state: &AppState, channel_id: Uuid, connected_at: DateTime<Utc>) -> (Uuid, mpsc::Receiver<PolledFrame>) {
+        let (sender, receiver) = mpsc::channel(8);
+        let mut connection = Connection::new(Uuid::new_v4(), "client".to_string(), Sink::Poll(sender));
+        connection.connected_at = connected_at;
+        let connection_id = connection.id;
+        state.connections.lock().await.insert(channel_id, connection);
+        (connection_id, receiver)
+    }
+
+    #[tokio::test]
+    async fn test_lowering_max_connections_closes_newest() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = channel_with_messages(&state, 0).await;
+        let now = Utc::now();
+        // Los receptores siguen vivos: solo el límite puede cerrar conexiones
+        let (oldest, _oldest_frames) = subscribe(&state, channel.id, now - Duration::seconds(30)).await;
+        let (middle, _middle_frames) = subscribe(&state, channel.id, now - Duration::seconds(20)).await;
+        let (_, _newest_frames) = subscribe(&state, channel.id, now - Duration::seconds(10)).await;
+
+        let patch = ChannelSettingsPatch { max_connections: Some(2), ..ChannelSettingsPatch::default() };
+        let updated = ChannelService::update_channel(&state, channel.id, update(None, Some(patch))).await.unwrap();
+
+        assert_eq!(updated.settings.max_connections, 2);
+        let mut remaining: Vec<_> = state.list_connections(&channel.id).await.into_iter().map(|info| info.id).collect();
+        remaining.sort();
+        let mut expected = vec![oldest, middle];
+        expected.sort();
+        assert_eq!(remaining, expected);
+    }
+
+    #[tokio::test]
+    async fn test_toggle_client_messages() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = channel_with_messages(&state, 0).await;
+        let (_, mut frames) = subscribe(&state, channel.id, Utc::now()).await;
+
+        for allow in [false, true] {
+            let patch = ChannelSettingsPatch { allow_client_messages: Some(allow), ..ChannelSettingsPatch::default() };
+            ChannelService::update_channel(&state, channel.id, update(None, Some(patch))).await.unwrap();
+
+            assert_eq!(state.get_channel(&channel.id).await.unwrap().settings.allow_client_messages, allow);
+            let stored = state.load_channel(&channel.id).await.unwrap().unwrap();
+            assert_eq!(stored.settings.allow_client_messages, allow);
+
+            // Las sesiones conectadas reciben qué cambió
+            let frame: serde_json::Value = serde_json::from_str(&frames.try_recv().unwrap().frame).unwrap();
+            assert_eq!(frame["data"]["details"]["changes"], serde_json::json!(["settings.allow_client_messages"]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_update_changes_nothing() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = channel_with_messages(&state, 0).await;
+
+        let empty_name = ChannelService::update_channel(&state, channel.id, update(Some("  "), None)).await;
+        assert!(empty_name.is_err());
+
+        // Un nombre válido junto a `max_connections = 0` tampoco se aplica
+        let patch = ChannelSettingsPatch { max_connections: Some(0), ..ChannelSettingsPatch::default() };
+        let no_connections = ChannelService::update_channel(&state, channel.id, update(Some("renamed"), Some(patch))).await;
+        assert!(no_connections.is_err());
 
-    pub async fn list_channels(state: &AppState) -> Vec<Channel> {
-        state.active_channels.read().await.values().cloned().collect()
+        let loaded = state.get_channel(&channel.id).await.unwrap();
+        assert_eq!((loaded.name, loaded.settings.max_connections), (channel.name.clone(), channel.settings.max_connections));
+        assert_eq!(state.load_channel(&channel.id).await.unwrap().unwrap().name, channel.name);
     }
-}
\ No newline at end of file
+}
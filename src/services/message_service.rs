@@ -1,7 +1,15 @@
-use crate::models::message::{BroadcastMessage, BroadcastRequest, MessageSender, MessageType};
-use crate::state::AppState;
+use crate::models::message::{
+    is_valid_content_type, BroadcastMessage, BroadcastRequest, Delivery, MessageHistoryPage,
+    MessageHistoryQuery, MessageSender, MessageType, MAX_MESSAGE_HEADERS,
+};
+use crate::error::AppError;
+use crate::models::channel::{Channel, ChannelStatus, PausePolicy};
+use crate::services::channel_service::ChannelService;
+use crate::state::{AppState, PublishOrder};
+use crate::utils::rate_limit::RateLimitKey;
 use anyhow::Result;
 use chrono::Utc;
+use std::collections::{BTreeMap, VecDeque};
 use uuid::Uuid;
 
 pub struct MessageService;
@@ -11,50 +19,113 @@ impl MessageService {
         state: &AppState,
         channel_id: Uuid,
         request: BroadcastRequest,
-    ) -> Result<(BroadcastMessage, usize)> {
-        // Verificar que el canal existe y está activo
-        let channel = state
-            .get_channel(&channel_id)
-            .await
-            .ok_or_else(|| anyhow::anyhow!("Channel not found"))?;
-
-        if !matches!(
-            channel.status,
-            crate::models::channel::ChannelStatus::Active
-        ) {
-            anyhow::bail!("Channel is not active");
-        }
+    ) -> Result<(BroadcastMessage, usize, Delivery)> {
+        let (content, data) = match (request.content, request.data) {
+            (Some(content), None) => (content, None),
+            (None, Some(data)) => (String::new(), Some(data)),
+            _ => anyhow::bail!("Provide exactly one of 'content' or 'data'"),
+        };
+        Self::validate_headers(&request.headers)?;
 
-        // Crear mensaje
         let message = BroadcastMessage {
             id: Uuid::new_v4(),
             channel_id,
-            content: request.content,
+            content,
             message_type: request.message_type.unwrap_or(MessageType::Broadcast),
             sender: MessageSender::Server,
             timestamp: Utc::now(),
+            seq: 0,
+            content_type: None,
+            payload: None,
+            data,
+            headers: request.headers,
         };
 
-        // Persistir mensaje si está configurado
-        if channel.settings.persist_messages {
-            state.save_message(&message).await?;
+        Self::deliver(state, message).await
+    }
+
+    /// Publica un contenido binario recibido como cuerpo en bruto de la petición
+    pub async fn broadcast_binary(
+        state: &AppState,
+        channel_id: Uuid,
+        content_type: &str,
+        payload: Vec<u8>,
+    ) -> Result<(BroadcastMessage, usize, Delivery)> {
+        if !is_valid_content_type(content_type) {
+            anyhow::bail!("Invalid content type '{}'", content_type);
         }
 
-        // Crear respuesta WebSocket
-        let ws_response = crate::models::message::WebSocketResponse {
-            status: "broadcast".to_string(),
-            message: message.content.clone(),
-            channel_id: message.channel_id,
-            timestamp: message.timestamp,
+        let message = BroadcastMessage {
+            id: Uuid::new_v4(),
+            channel_id,
+            content: String::new(),
+            message_type: MessageType::Broadcast,
+            sender: MessageSender::Server,
+            timestamp: Utc::now(),
+            seq: 0,
+            content_type: Some(content_type.to_string()),
+            payload: Some(payload),
             data: None,
+            headers: BTreeMap::new(),
         };
 
-        let json_message = serde_json::to_string(&ws_response)?;
+        Self::deliver(state, message).await
+    }
+
+    pub fn validate_headers(headers: &BTreeMap<String, String>) -> Result<()> {
+        if headers.len() > MAX_MESSAGE_HEADERS {
+            anyhow::bail!("At most {} headers are allowed per message", MAX_MESSAGE_HEADERS);
+        }
+        if headers.keys().any(|name| name.trim().is_empty()) {
+            anyhow::bail!("Header names cannot be empty");
+        }
+        Ok(())
+    }
 
-        // Enviar a todos los clientes del canal
-        let sent_count = state
-            .broadcast_to_channel(&channel_id, &json_message)
-            .await?;
+    async fn deliver(
+        state: &AppState,
+        message: BroadcastMessage,
+    ) -> Result<(BroadcastMessage, usize, Delivery)> {
+        let channel_id = message.channel_id;
+
+        // Verificar que el canal existe y acepta publicaciones
+        let channel = state
+            .get_channel(&channel_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Channel not found"))?;
+
+        let accepted_while_paused = Self::accepts_publish(&channel)?;
+
+        let size_limit = state.message_size_limit_for(&channel);
+        if message.size() > size_limit {
+            return Err(AppError::PayloadTooLarge {
+                size: message.size(),
+                limit: size_limit,
+            }
+            .into());
+        }
+
+        if let Some(per_minute) = channel.settings.rate_limit_per_minute {
+            state
+                .rate_limiter
+                .check(RateLimitKey::Channel(channel_id), per_minute)
+                .map_err(|retry_after| AppError::RateLimited { retry_after })?;
+        }
+
+        if accepted_while_paused
+            && let Some(delivery) = Self::hold_while_paused(state, &message).await?
+        {
+            tracing::info!(
+                "Message {:?} for paused channel {} ({})",
+                delivery,
+                channel.name,
+                channel_id
+            );
+            return Ok((message, 0, delivery));
+        }
+
+        // Numerar, persistir si está configurado y enviar a todos los clientes del canal
+        let (message, sent_count) = state.publish(&channel, message).await?;
 
         tracing::info!(
             "Broadcasted message to {} clients in channel {} ({})",
@@ -63,6 +134,241 @@ impl MessageService {
             channel_id
         );
 
-        Ok((message, sent_count))
+        Ok((message, sent_count, Delivery::Delivered))
+    }
+
+    /// Comprueba que el canal admite publicaciones. Devuelve `true` si está en pausa con una
+    /// política que acepta el mensaje, que entonces debe pasar por `hold_while_paused`.
+    pub fn accepts_publish(channel: &Channel) -> Result<bool> {
+        let accepted_while_paused = channel.status == ChannelStatus::Paused
+            && channel.settings.pause_policy != PausePolicy::Reject;
+
+        if channel.status != ChannelStatus::Active && !accepted_while_paused {
+            anyhow::bail!("Channel is not active");
+        }
+        Ok(accepted_while_paused)
+    }
+
+    /// Aplica la política de pausa bajo el lock de canales para no competir con `start_channel`.
+    /// Devuelve `None` si el canal se reanudó entretanto y el mensaje debe entregarse ya.
+    pub async fn hold_while_paused(
+        state: &AppState,
+        message: &BroadcastMessage,
+    ) -> Result<Option<Delivery>> {
+        let mut active_channels = state.active_channels.write().await;
+        let channel = active_channels
+            .get_mut(&message.channel_id)
+            .ok_or_else(|| anyhow::anyhow!("Channel not found"))?;
+
+        match (channel.status, channel.settings.pause_policy) {
+            (ChannelStatus::Active, _) => Ok(None),
+            (ChannelStatus::Paused, PausePolicy::Drop) => Ok(Some(Delivery::Dropped)),
+            (ChannelStatus::Paused, PausePolicy::Buffer { max_messages, max_bytes }) => {
+                let size = message.size();
+                if channel.buffered.messages >= max_messages
+                    || channel.buffered.bytes + size > max_bytes
+                {
+                    return Err(AppError::PauseBufferFull { max_messages, max_bytes }.into());
+                }
+
+                state
+                    .pause_buffers
+                    .lock()
+                    .await
+                    .entry(channel.id)
+                    .or_default()
+                    .push_back(message.clone());
+                channel.buffered.messages += 1;
+                channel.buffered.bytes += size;
+
+                Ok(Some(Delivery::Buffered))
+            }
+            _ => anyhow::bail!("Channel is not active"),
+        }
+    }
+
+    /// Entrega en orden los mensajes retenidos durante la pausa. La secuencia y la
+    /// persistencia se asignan al entregarlos, no al retenerlos. Con `order` tomado,
+    /// las publicaciones nuevas esperan a que termine el vaciado.
+    pub async fn flush_pause_buffer(
+        state: &AppState,
+        order: &PublishOrder,
+        channel: &Channel,
+        messages: VecDeque<BroadcastMessage>,
+    ) -> Result<usize> {
+        let count = messages.len();
+
+        for message in messages {
+            state.publish_ordered(order, channel, message).await?;
+        }
+
+        tracing::info!(
+            "Flushed {} buffered messages to channel {} ({})",
+            count,
+            channel.name,
+            channel.id
+        );
+        Ok(count)
+    }
+
+    pub async fn get_history(
+        state: &AppState,
+        channel_id: Uuid,
+        query: MessageHistoryQuery,
+    ) -> Result<MessageHistoryPage> {
+        // Los canales detenidos no están en memoria tras un reinicio pero su historial sí existe
+        ChannelService::get_channel(state, channel_id).await?;
+
+        if let (Some(since), Some(until)) = (&query.since, &query.until)
+            && since > until
+        {
+            anyhow::bail!("'since' must be earlier than 'until'");
+        }
+
+        state.get_channel_messages(&channel_id, &query).await
+    }
+
+    /// Envía un frame `StatusUpdate` a todas las sesiones del canal
+    pub async fn send_status_update(
+        state: &AppState,
+        channel: &Channel,
+        event: &str,
+        details: serde_json::Value,
+    ) -> Result<usize> {
+        let ws_response = crate::models::message::WebSocketResponse {
+            status: "status_update".to_string(),
+            message: format!("Channel {} {}", channel.name, event),
+            channel_id: channel.id,
+            timestamp: Utc::now(),
+            seq: None,
+            data: Some(serde_json::json!({
+                "message_type": MessageType::StatusUpdate,
+                "sender": MessageSender::System,
+                "event": event,
+                "channel_status": channel.status,
+                "details": details
+            })),
+        };
+
+        state.broadcast_to_channel(&channel.id, &ws_response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::channel::ChannelSettings;
+    use crate::utils::test_support::{loaded_channel, test_state, TempDir};
+
+    async fn paused_channel(state: &AppState, pause_policy: PausePolicy) -> Channel {
+        let settings = ChannelSettings {
+            persist_messages: true,
+            rate_limit_per_minute: None,
+            pause_policy,
+            ..ChannelSettings::default()
+        };
+        loaded_channel(state, ChannelStatus::Paused, settings).await
+    }
+
+    fn text(content: &str) -> BroadcastRequest {
+        BroadcastRequest {
+            content: Some(content.to_string()),
+            data: None,
+            headers: BTreeMap::new(),
+            message_type: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pause_buffer_limits() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let policy = PausePolicy::Buffer { max_messages: 2, max_bytes: 1024 * 1024 };
+        let channel = paused_channel(&state, policy).await;
+
+        for content in ["a", "b"] {
+            let (_, sent, delivery) = MessageService::broadcast_message(&state, channel.id, text(content))
+                .await
+                .unwrap();
+            assert_eq!((sent, delivery), (0, Delivery::Buffered));
+        }
+
+        let full = MessageService::broadcast_message(&state, channel.id, text("c")).await.unwrap_err();
+        assert!(matches!(full.downcast_ref::<AppError>(), Some(AppError::PauseBufferFull { .. })));
+
+        let depth = state.get_channel(&channel.id).await.unwrap().buffered;
+        assert_eq!(depth.messages, 2);
+
+        // La profundidad del buffer no se guarda en redb
+        assert_eq!(state.load_channel(&channel.id).await.unwrap().unwrap().buffered.messages, 0);
+
+        // Un límite de bytes menor que el mensaje lo rechaza aunque quede sitio en número
+        let tiny = paused_channel(&state, PausePolicy::Buffer { max_messages: 10, max_bytes: 1 }).await;
+        let too_big = MessageService::broadcast_message(&state, tiny.id, text("abc")).await.unwrap_err();
+        assert!(matches!(too_big.downcast_ref::<AppError>(), Some(AppError::PauseBufferFull { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_pause_drop_policy_discards() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = paused_channel(&state, PausePolicy::Drop).await;
+
+        let (_, sent, delivery) = MessageService::broadcast_message(&state, channel.id, text("lost"))
+            .await
+            .unwrap();
+        assert_eq!((sent, delivery), (0, Delivery::Dropped));
+
+        ChannelService::start_channel(&state, channel.id, "admin", None).await.unwrap();
+        assert_eq!(state.last_seq(&channel.id).await.unwrap(), 0);
+        assert!(state.take_pause_buffer(&channel.id).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pause_buffer_flushes_in_order() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let policy = PausePolicy::Buffer { max_messages: 10, max_bytes: 1024 * 1024 };
+        let channel = paused_channel(&state, policy).await;
+
+        for content in ["a", "b", "c"] {
+            MessageService::broadcast_message(&state, channel.id, text(content)).await.unwrap();
+        }
+
+        let started = ChannelService::start_channel(&state, channel.id, "admin", None).await.unwrap();
+        assert_eq!(started.buffered.messages, 0);
+
+        // Lo publicado tras reanudar va detrás de lo retenido
+        let (after, _, delivery) = MessageService::broadcast_message(&state, channel.id, text("d"))
+            .await
+            .unwrap();
+        assert_eq!((after.seq, delivery), (4, Delivery::Delivered));
+
+        let stored = state.messages_after_seq(&channel.id, 0, 10).unwrap();
+        let contents: Vec<_> = stored.iter().map(|message| (message.seq, message.content.as_str())).collect();
+        assert_eq!(contents, [(1, "a"), (2, "b"), (3, "c"), (4, "d")]);
+    }
+
+    #[tokio::test]
+    async fn test_history_of_unloaded_channel() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel = paused_channel(&state, PausePolicy::Reject).await;
+        ChannelService::start_channel(&state, channel.id, "admin", None).await.unwrap();
+        MessageService::broadcast_message(&state, channel.id, text("kept")).await.unwrap();
+        ChannelService::stop_channel(&state, channel.id, "admin", None).await.unwrap();
+
+        // Tras un reinicio los canales detenidos solo están en redb
+        state.active_channels.write().await.remove(&channel.id);
+
+        let page = MessageService::get_history(&state, channel.id, MessageHistoryQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(page.messages.len(), 1);
+
+        let missing = MessageService::get_history(&state, Uuid::new_v4(), MessageHistoryQuery::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(missing.downcast_ref::<AppError>(), Some(AppError::ChannelNotFound)));
     }
-}
\ No newline at end of file
+}
@@ -1,2 +1,6 @@
+pub mod auth_service;
+pub mod backup_service;
 pub mod channel_service;
-pub mod message_service;
\ No newline at end of file
+pub mod message_service;
+pub mod retention_service;
+pub mod token_service;
\ No newline at end of file
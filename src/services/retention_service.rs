@@ -0,0 +1,63 @@
+use crate::models::admin::RetentionReport;
+use crate::state::AppState;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use std::sync::Arc;
+
+pub struct RetentionService;
+
+impl RetentionService {
+    /// Lanza la tarea en segundo plano que aplica la retención según `PersistenceConfig`
+    pub fn spawn(state: Arc<AppState>) {
+        let interval_minutes = state.config.persistence.retention_interval_minutes;
+
+        actix_web::rt::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_minutes * 60));
+
+            loop {
+                interval.tick().await;
+
+                if let Err(e) = Self::run(&state).await {
+                    tracing::error!("Message retention run failed: {}", e);
+                }
+            }
+        });
+
+        tracing::info!("Message retention janitor scheduled every {} minutes", interval_minutes);
+    }
+
+    pub async fn run(state: &AppState) -> Result<RetentionReport> {
+        let persistence = &state.config.persistence;
+        let started_at = Utc::now();
+        let cutoff = started_at - Duration::days(persistence.message_retention_days as i64);
+
+        let stats = state
+            .purge_messages(cutoff, persistence.max_messages_per_channel)
+            .await?;
+
+        let report = RetentionReport {
+            started_at,
+            finished_at: Utc::now(),
+            retention_days: persistence.message_retention_days,
+            max_messages_per_channel: persistence.max_messages_per_channel,
+            expired_removed: stats.expired_removed,
+            trimmed_removed: stats.trimmed_removed,
+            channels_trimmed: stats.channels_trimmed,
+        };
+
+        if report.total_removed() > 0 {
+            tracing::info!(
+                "Message retention removed {} messages ({} expired, {} trimmed across {} channels)",
+                report.total_removed(),
+                report.expired_removed,
+                report.trimmed_removed,
+                report.channels_trimmed
+            );
+        } else {
+            tracing::debug!("Message retention run found nothing to remove");
+        }
+
+        *state.last_retention_report.write().await = Some(report.clone());
+        Ok(report)
+    }
+}
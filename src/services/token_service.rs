@@ -0,0 +1,145 @@
+use crate::models::auth::{CreateSubscriberTokenRequest, SubscriberClaims, SubscriberToken, TokenAction};
+use crate::state::AppState;
+use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub struct TokenService;
+
+impl TokenService {
+    /// Emite un token firmado para que `client_id` se conecte a `channel_id`
+    pub async fn issue(
+        state: &AppState,
+        channel_id: Uuid,
+        request: CreateSubscriberTokenRequest,
+    ) -> Result<SubscriberToken> {
+        state
+            .get_channel(&channel_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Channel not found"))?;
+
+        if request.client_id.trim().is_empty() {
+            anyhow::bail!("client_id cannot be empty");
+        }
+
+        let auth = &state.config.auth;
+        let ttl = request.ttl_seconds.unwrap_or(auth.token_ttl_seconds);
+        if ttl == 0 || ttl > auth.max_token_ttl_seconds {
+            anyhow::bail!("ttl_seconds must be between 1 and {}", auth.max_token_ttl_seconds);
+        }
+
+        let actions = request.actions.unwrap_or_else(|| vec![TokenAction::Subscribe]);
+        if actions.is_empty() {
+            anyhow::bail!("Token needs at least one action");
+        }
+
+        let expires_at = Utc::now() + chrono::Duration::seconds(ttl as i64);
+        let claims = SubscriberClaims {
+            channel_id,
+            sub: request.client_id,
+            exp: expires_at.timestamp(),
+            actions,
+        };
+
+        let token = Self::sign(&state.token_secret, &claims)?;
+
+        tracing::info!("Issued subscriber token for {} in channel {}", claims.sub, channel_id);
+        Ok(SubscriberToken {
+            token,
+            expires_at: DateTime::from_timestamp(claims.exp, 0).unwrap_or(expires_at),
+            claims,
+        })
+    }
+
+    /// Verifica firma, expiración y canal de un token
+    pub fn verify(state: &AppState, token: &str, channel_id: &Uuid) -> Result<SubscriberClaims> {
+        let claims = Self::decode(&state.token_secret, token, Utc::now().timestamp())?;
+
+        if claims.channel_id != *channel_id {
+            anyhow::bail!("Token was issued for another channel");
+        }
+
+        Ok(claims)
+    }
+
+    fn sign(secret: &[u8], claims: &SubscriberClaims) -> Result<String> {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?);
+
+        let mut mac = HmacSha256::new_from_slice(secret)?;
+        mac.update(payload.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        Ok(format!("{}.{}", payload, signature))
+    }
+
+    fn decode(secret: &[u8], token: &str, now: i64) -> Result<SubscriberClaims> {
+        let (payload, signature) = token
+            .split_once('.')
+            .ok_or_else(|| anyhow::anyhow!("Malformed token"))?;
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| anyhow::anyhow!("Malformed token"))?;
+
+        let mut mac = HmacSha256::new_from_slice(secret)?;
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| anyhow::anyhow!("Invalid token signature"))?;
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| anyhow::anyhow!("Malformed token"))?;
+        let claims: SubscriberClaims = serde_json::from_slice(&payload)?;
+
+        if claims.exp <= now {
+            anyhow::bail!("Token expired");
+        }
+
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(exp: i64) -> SubscriberClaims {
+        SubscriberClaims {
+            channel_id: Uuid::new_v4(),
+            sub: "client-1".to_string(),
+            exp,
+            actions: vec![TokenAction::Subscribe],
+        }
+    }
+
+    #[test]
+    fn test_token_roundtrip() {
+        let secret = b"0123456789abcdef0123456789abcdef";
+        let token = TokenService::sign(secret, &claims(2_000)).unwrap();
+
+        let decoded = TokenService::decode(secret, &token, 1_000).unwrap();
+        assert_eq!(decoded.sub, "client-1");
+        assert!(decoded.allows(TokenAction::Subscribe));
+        assert!(!decoded.allows(TokenAction::Publish));
+    }
+
+    #[test]
+    fn test_token_rejects_tampering_and_expiry() {
+        let secret = b"0123456789abcdef0123456789abcdef";
+        let token = TokenService::sign(secret, &claims(2_000)).unwrap();
+
+        assert!(TokenService::decode(b"another-secret-another-secret-xx", &token, 1_000).is_err());
+        assert!(TokenService::decode(secret, &token, 2_000).is_err());
+
+        let forged = TokenService::sign(b"another-secret-another-secret-xx", &claims(9_999)).unwrap();
+        let (forged_payload, _) = forged.split_once('.').unwrap();
+        let (_, signature) = token.split_once('.').unwrap();
+        assert!(TokenService::decode(secret, &format!("{}.{}", forged_payload, signature), 1_000).is_err());
+    }
+}
@@ -1,46 +1,646 @@
-use crate::models::{channel::Channel, message::BroadcastMessage};
-use actix_ws::Session;
+use crate::config::Config;
+use crate::error::AppError;
+use crate::utils::rate_limit::{RateLimitKey, RateLimiter};
+use crate::utils::sse::{send_event, try_send_event, SseEvent};
+use crate::models::{admin::RetentionReport, auth::ApiKey, channel::{BufferDepth, Channel, ChannelStatus, ChannelTransition}, message::{BroadcastMessage, FrameFormat, MessageHistoryPage, MessageHistoryQuery, WebSocketResponse}, protocol::ServerFrame};
+use actix_web::web::Bytes;
+use actix_ws::{CloseCode, CloseReason, Closed, Session};
 use anyhow::Result;
-use redb::{Database, ReadableTable, TableDefinition};
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::Serialize;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::Bound;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, Mutex, OwnedMutexGuard, RwLock};
+use tokio::task::AbortHandle;
 use uuid::Uuid;
 
 pub const CHANNELS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("channels");
 pub const MESSAGES_TABLE: TableDefinition<&str, &str> = TableDefinition::new("messages");
+/// API keys por hash SHA-256 del secreto
+pub const API_KEYS_TABLE: TableDefinition<&str, &str> = TableDefinition::new("api_keys");
+/// Índice por canal ordenado en el tiempo: `{channel_id}/{timestamp_micros}/{message_id}` -> `message_id`
+pub const CHANNEL_MESSAGES_TABLE: TableDefinition<&str, &str> = TableDefinition::new("channel_messages");
+
+/// Índice por secuencia: `{channel_id}/{seq}` -> `message_id`
+pub const CHANNEL_SEQ_TABLE: TableDefinition<&str, &str> = TableDefinition::new("channel_seq");
+/// Contenido de los mensajes binarios: `message_id` -> bytes, fuera del JSON de `MESSAGES_TABLE`
+pub const MESSAGE_PAYLOADS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("message_payloads");
+/// Secuencia hasta la que cada canal tiene números reservados, se persistan o no sus mensajes
+pub const CHANNEL_SEQ_COUNTERS_TABLE: TableDefinition<&str, u64> = TableDefinition::new("channel_seq_counters");
+/// Transiciones de estado por canal: `{channel_id}/{timestamp_micros}/{uuid}` -> `ChannelTransition`
+pub const CHANNEL_HISTORY_TABLE: TableDefinition<&str, &str> = TableDefinition::new("channel_history");
+
+/// Secuencias que se reservan en redb de una vez. Tras un reinicio la numeración sigue al
+/// final del bloque reservado, sin una escritura durable por mensaje.
+pub const SEQ_RESERVATION_BLOCK: u64 = 1000;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 50;
+pub const MAX_HISTORY_LIMIT: usize = 500;
+
+/// Construye la clave del índice por canal. El timestamp va con padding
+/// para que el orden lexicográfico de redb coincida con el orden temporal.
+pub fn message_index_key(channel_id: &Uuid, timestamp: &DateTime<Utc>, message_id: &Uuid) -> String {
+    format!("{}/{:020}/{}", channel_id, timestamp.timestamp_micros().max(0), message_id)
+}
+
+fn seq_index_key(channel_id: &Uuid, seq: u64) -> String {
+    format!("{}/{:020}", channel_id, seq)
+}
+
+fn message_index_bound(channel_id: &Uuid, timestamp: &DateTime<Utc>) -> String {
+    format!("{}/{:020}", channel_id, timestamp.timestamp_micros().max(0))
+}
+
+/// Borra un mensaje, su entrada en el índice por secuencia y su contenido binario
+fn remove_message(
+    messages: &mut redb::Table<&str, &str>,
+    seq_index: &mut redb::Table<&str, &str>,
+    payloads: &mut redb::Table<&str, &[u8]>,
+    message_id: &str,
+) -> Result<()> {
+    let removed = messages.remove(message_id)?;
+
+    if let Some(message_json) = removed {
+        let message: BroadcastMessage = serde_json::from_str(message_json.value())?;
+        if message.seq > 0 {
+            seq_index.remove(seq_index_key(&message.channel_id, message.seq).as_str())?;
+        }
+        if message.is_binary() {
+            payloads.remove(message_id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// JSON de un canal para `CHANNELS_TABLE`. La profundidad del buffer de pausa solo
+/// tiene sentido en memoria y no se guarda.
+fn stored_channel_json(channel: &Channel) -> Result<String> {
+    let mut value = serde_json::to_value(channel)?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.remove("buffered");
+    }
+    Ok(serde_json::to_string(&value)?)
+}
+
+/// Lee un mensaje guardado junto con su contenido binario, si lo tiene
+fn load_message(
+    messages: &impl ReadableTable<&'static str, &'static str>,
+    payloads: &impl ReadableTable<&'static str, &'static [u8]>,
+    message_id: &str,
+) -> Result<Option<BroadcastMessage>> {
+    let Some(message_json) = messages.get(message_id)? else {
+        return Ok(None);
+    };
+
+    let mut message: BroadcastMessage = serde_json::from_str(message_json.value())?;
+    if message.is_binary() {
+        message.payload = payloads.get(message_id)?.map(|payload| payload.value().to_vec());
+    }
+
+    Ok(Some(message))
+}
+
+/// Conteo de filas eliminadas por `AppState::purge_messages`
+#[derive(Debug, Default)]
+pub struct PurgeStats {
+    pub expired_removed: usize,
+    pub trimmed_removed: usize,
+    pub channels_trimmed: usize,
+}
+
+/// Secuencia y mensajes recientes de un canal para reanudar suscripciones
+#[derive(Debug, Default)]
+pub struct ReplayLog {
+    pub last_seq: u64,
+    /// Hasta dónde llega la reserva guardada en `CHANNEL_SEQ_COUNTERS_TABLE`
+    pub reserved_seq: u64,
+    pub recent: VecDeque<BroadcastMessage>,
+}
+
+/// Frame listo para entregar por cualquier transporte
+#[derive(Debug)]
+pub struct Frame {
+    /// Secuencia del mensaje; `id:` del evento en SSE
+    pub seq: Option<u64>,
+    /// Frame JSON; el contenido binario va en base64
+    pub json: String,
+    /// En WebSocket un mensaje binario llega como cabecera JSON seguida de un frame binario
+    pub binary: Option<(String, Bytes)>,
+}
+
+impl Frame {
+    pub fn json(json: String) -> Self {
+        Self { seq: None, json, binary: None }
+    }
+
+    pub fn message(message: &BroadcastMessage, channel_name: &str, format: FrameFormat) -> Result<Self> {
+        let binary = match &message.payload {
+            Some(payload) => Some((message.to_binary_header(channel_name, format)?, Bytes::copy_from_slice(payload))),
+            None => None,
+        };
+
+        Ok(Self {
+            seq: Some(message.seq),
+            json: message.to_frame(channel_name, format)?,
+            binary,
+        })
+    }
+
+    /// Bytes que recibe un cliente WebSocket; en SSE y long-polling el binario viaja en el JSON
+    pub fn size(&self) -> usize {
+        match &self.binary {
+            Some((header, payload)) => header.len() + payload.len(),
+            None => self.json.len(),
+        }
+    }
+}
+
+/// Contenido para todas las sesiones de un canal; los mensajes se renderizan en el formato de cada una
+enum Outgoing<'a> {
+    Control(&'a WebSocketResponse),
+    Message { message: &'a BroadcastMessage, channel_name: &'a str },
+}
+
+/// Frame recogido para una petición de long-polling
+#[derive(Debug)]
+pub struct PolledFrame {
+    pub seq: Option<u64>,
+    pub frame: String,
+}
+
+/// Frames WebSocket en cola por conexión; con la cola llena el fan-out da de baja al cliente
+pub const SESSION_QUEUE_CAPACITY: usize = 64;
+
+/// Escritura pendiente en una sesión WebSocket
+enum Outbound {
+    Text(String),
+    /// Cabecera JSON y contenido binario de un mismo mensaje, sin nada entre medias
+    TextAndBinary(String, Bytes),
+    Ping(Bytes),
+    Pong(Bytes),
+    Close(Option<CloseReason>),
+}
+
+/// Cola de escritura de una sesión WebSocket, compartida entre la tarea de la conexión y
+/// el fan-out. Una única tarea vacía la cola en orden sobre la `Session`, así que el
+/// fan-out encola sin esperar nunca a un cliente que no lee.
+#[derive(Clone)]
+pub struct SessionWriter {
+    queue: mpsc::Sender<Outbound>,
+    writer: AbortHandle,
+}
+
+impl SessionWriter {
+    pub fn new(session: Session) -> Self {
+        let (queue, receiver) = mpsc::channel(SESSION_QUEUE_CAPACITY);
+        let writer = tokio::spawn(write_session(session, receiver)).abort_handle();
+        Self { queue, writer }
+    }
+
+    async fn push(&self, outbound: Outbound) -> Result<(), Closed> {
+        self.queue.send(outbound).await.map_err(|_| Closed)
+    }
+
+    pub async fn text(&self, text: String) -> Result<(), Closed> {
+        self.push(Outbound::Text(text)).await
+    }
+
+    /// Cabecera JSON y contenido binario seguidos, sin escrituras de otros entre medias
+    pub async fn text_and_binary(&self, header: String, payload: Bytes) -> Result<(), Closed> {
+        self.push(Outbound::TextAndBinary(header, payload)).await
+    }
+
+    pub async fn ping(&self, payload: &[u8]) -> Result<(), Closed> {
+        self.push(Outbound::Ping(Bytes::copy_from_slice(payload))).await
+    }
+
+    pub async fn pong(&self, payload: &[u8]) -> Result<(), Closed> {
+        self.push(Outbound::Pong(Bytes::copy_from_slice(payload))).await
+    }
+
+    /// Encola un frame sin esperar; falla si el cliente no vacía su cola
+    pub fn offer(&self, frame: &Frame) -> Result<()> {
+        let outbound = match &frame.binary {
+            Some((header, payload)) => Outbound::TextAndBinary(header.clone(), payload.clone()),
+            None => Outbound::Text(frame.json.clone()),
+        };
+
+        self.queue.try_send(outbound).map_err(|e| match e {
+            TrySendError::Full(_) => anyhow::anyhow!("WebSocket client is too slow"),
+            TrySendError::Closed(_) => anyhow::anyhow!("WebSocket client disconnected"),
+        })
+    }
+
+    /// Cierra tras lo ya encolado. Si la cola está llena el cliente no está leyendo y
+    /// se corta la sesión sin esperarle.
+    pub fn close(&self, reason: Option<CloseReason>) {
+        if self.queue.try_send(Outbound::Close(reason)).is_err() {
+            self.writer.abort();
+        }
+    }
+}
+
+/// Vacía la cola de una sesión hasta que se cierra o el cliente se desconecta
+async fn write_session(mut session: Session, mut queue: mpsc::Receiver<Outbound>) {
+    while let Some(outbound) = queue.recv().await {
+        let result = match outbound {
+            Outbound::Text(text) => session.text(text).await,
+            Outbound::TextAndBinary(header, payload) => match session.text(header).await {
+                Ok(()) => session.binary(payload).await,
+                Err(e) => Err(e),
+            },
+            Outbound::Ping(payload) => session.ping(&payload).await,
+            Outbound::Pong(payload) => session.pong(&payload).await,
+            Outbound::Close(reason) => {
+                let _ = session.close(reason).await;
+                return;
+            }
+        };
+
+        if result.is_err() {
+            return;
+        }
+    }
+}
+
+/// Transporte por el que una conexión recibe los frames de su canal
+#[derive(Clone)]
+pub enum Sink {
+    WebSocket(SessionWriter),
+    Sse(mpsc::Sender<SseEvent>),
+    Poll(mpsc::Sender<PolledFrame>),
+}
+
+impl Sink {
+    /// Envía un frame JSON; `seq` se usa como `id:` del evento en SSE
+    pub async fn send(&mut self, frame: &str, seq: Option<u64>) -> Result<()> {
+        match self {
+            Sink::WebSocket(session) => session.text(frame.to_string()).await?,
+            Sink::Sse(sender) => send_event(sender, SseEvent::Message { id: seq, data: frame.to_string() }).await?,
+            Sink::Poll(sender) => sender
+                .send(PolledFrame { seq, frame: frame.to_string() })
+                .await
+                .map_err(|_| anyhow::anyhow!("Poll request finished"))?,
+        }
+        Ok(())
+    }
+
+    /// Envía un frame completo; solo WebSocket recibe el contenido binario sin codificar
+    pub async fn deliver(&mut self, frame: &Frame) -> Result<()> {
+        match (self, &frame.binary) {
+            (Sink::WebSocket(session), Some((header, payload))) => {
+                session.text_and_binary(header.clone(), payload.clone()).await?;
+                Ok(())
+            }
+            (sink, _) => sink.send(&frame.json, frame.seq).await,
+        }
+    }
+
+    /// Entrega del fan-out en vivo: nunca espera al cliente. Si su cola está llena
+    /// falla y el cliente se da de baja.
+    pub fn offer(&self, frame: &Frame) -> Result<()> {
+        match self {
+            Sink::WebSocket(session) => session.offer(frame),
+            Sink::Sse(sender) => try_send_event(sender, SseEvent::Message { id: frame.seq, data: frame.json.clone() }),
+            Sink::Poll(sender) => sender
+                .try_send(PolledFrame { seq: frame.seq, frame: frame.json.clone() })
+                .map_err(|e| match e {
+                    TrySendError::Full(_) => anyhow::anyhow!("Poll request is full"),
+                    TrySendError::Closed(_) => anyhow::anyhow!("Poll request finished"),
+                }),
+        }
+    }
+
+    /// Cierra el transporte sin esperar al cliente
+    pub fn close(self, reason: CloseReason) {
+        match self {
+            Sink::WebSocket(session) => session.close(Some(reason)),
+            Sink::Sse(sender) => {
+                let _ = try_send_event(&sender, SseEvent::Close);
+            }
+            // Soltar el emisor termina la espera de la petición
+            Sink::Poll(_) => {}
+        }
+    }
+
+    pub fn transport(&self) -> &'static str {
+        match self {
+            Sink::WebSocket(_) => "websocket",
+            Sink::Sse(_) => "sse",
+            Sink::Poll(_) => "poll",
+        }
+    }
+}
+
+/// Datos del cliente tomados de la petición que abrió la conexión
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClientMeta {
+    pub remote_addr: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// Suscriptor registrado en un canal
+#[derive(Clone)]
+pub struct Connection {
+    pub id: Uuid,
+    /// Identidad del cliente según su token, o `anonymous`
+    pub client_id: String,
+    pub sink: Sink,
+    pub meta: ClientMeta,
+    pub connected_at: DateTime<Utc>,
+    /// Último round-trip medido con el heartbeat del servidor
+    pub rtt_ms: Option<f64>,
+    /// Mientras se reenvían mensajes perdidos la conexión no recibe tráfico en vivo
+    pub replaying: bool,
+    pub format: FrameFormat,
+    /// Última secuencia confirmada con `ack` (protocolo v1)
+    pub acked_seq: Option<u64>,
+    /// Conexión del hub, suscrita a varios canales por el mismo transporte
+    pub multiplexed: bool,
+    /// En el hub, aviso a la sesión de que el servidor la dio de baja de un canal, para
+    /// que retire el permiso con que se suscribió
+    pub revoked: Option<mpsc::UnboundedSender<Uuid>>,
+    /// Frames del canal entregados a esta conexión, en vivo o reenviados
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+}
+
+impl Connection {
+    pub fn new(id: Uuid, client_id: String, sink: Sink) -> Self {
+        Self {
+            id,
+            client_id,
+            sink,
+            meta: ClientMeta::default(),
+            connected_at: Utc::now(),
+            rtt_ms: None,
+            replaying: false,
+            format: FrameFormat::default(),
+            acked_seq: None,
+            multiplexed: false,
+            revoked: None,
+            messages_sent: 0,
+            bytes_sent: 0,
+        }
+    }
+
+    pub fn info(&self, channel_id: Uuid) -> ConnectionInfo {
+        ConnectionInfo {
+            id: self.id,
+            channel_id,
+            client_id: self.client_id.clone(),
+            transport: self.sink.transport(),
+            format: self.format,
+            remote_addr: self.meta.remote_addr.clone(),
+            user_agent: self.meta.user_agent.clone(),
+            connected_at: self.connected_at,
+            rtt_ms: self.rtt_ms,
+            acked_seq: self.acked_seq,
+            messages_sent: self.messages_sent,
+            bytes_sent: self.bytes_sent,
+        }
+    }
+
+    /// Termina la suscripción al canal: cierra el transporte o, en el hub, solo avisa
+    /// de la baja para que la conexión siga atendiendo sus otros canales
+    pub fn end(self, channel_id: Uuid, reason: CloseReason) {
+        if !self.multiplexed {
+            self.sink.close(reason);
+            return;
+        }
+
+        if let Some(revoked) = &self.revoked {
+            let _ = revoked.send(channel_id);
+        }
+
+        let event = ServerFrame::Event {
+            event: "unsubscribed".to_string(),
+            channel_id: Some(channel_id),
+            timestamp: Utc::now(),
+            message: reason.description.clone().unwrap_or_default(),
+            data: Some(serde_json::json!({ "close_code": u16::from(reason.code) })),
+        };
+        if let Ok(json) = event.to_json() {
+            let _ = self.sink.offer(&Frame::json(json));
+        }
+    }
+}
+
+/// Conexiones por canal con el índice inverso de suscripciones por conexión.
+/// Una conexión del hub aparece con el mismo id en cada canal al que está suscrita.
+#[derive(Default)]
+pub struct ConnectionRegistry {
+    channels: HashMap<Uuid, HashMap<Uuid, Connection>>,
+    subscriptions: HashMap<Uuid, HashSet<Uuid>>,
+    /// Conexiones del hub desde que se aceptan, estén o no suscritas a algún canal
+    clients: HashMap<Uuid, AcceptedClient>,
+}
+
+/// Conexión del hub aceptada, antes y durante sus suscripciones
+#[derive(Debug, Clone)]
+pub struct AcceptedClient {
+    pub meta: ClientMeta,
+    pub connected_at: DateTime<Utc>,
+}
+
+impl ConnectionRegistry {
+    pub fn channel(&self, channel_id: &Uuid) -> Option<&HashMap<Uuid, Connection>> {
+        self.channels.get(channel_id)
+    }
+
+    pub fn get_mut(&mut self, channel_id: &Uuid, connection_id: &Uuid) -> Option<&mut Connection> {
+        self.channels.get_mut(channel_id)?.get_mut(connection_id)
+    }
+
+    pub fn count(&self, channel_id: &Uuid) -> usize {
+        self.channels.get(channel_id).map_or(0, HashMap::len)
+    }
+
+    pub fn insert(&mut self, channel_id: Uuid, connection: Connection) {
+        self.subscriptions.entry(connection.id).or_default().insert(channel_id);
+        self.channels.entry(channel_id).or_default().insert(connection.id, connection);
+    }
+
+    pub fn remove(&mut self, channel_id: &Uuid, connection_id: &Uuid) -> Option<Connection> {
+        let sessions = self.channels.get_mut(channel_id)?;
+        let removed = sessions.remove(connection_id);
+
+        if sessions.is_empty() {
+            self.channels.remove(channel_id);
+        }
+        if removed.is_some() {
+            self.unindex(channel_id, connection_id);
+        }
+
+        removed
+    }
+
+    pub fn remove_channel(&mut self, channel_id: &Uuid) -> Vec<Connection> {
+        let sessions = self.channels.remove(channel_id).unwrap_or_default();
+        for connection_id in sessions.keys() {
+            self.unindex(channel_id, connection_id);
+        }
+
+        sessions.into_values().collect()
+    }
+
+    /// Canales a los que está suscrita una conexión
+    pub fn subscriptions(&self, connection_id: &Uuid) -> Vec<Uuid> {
+        self.subscriptions
+            .get(connection_id)
+            .map(|channels| channels.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn unindex(&mut self, channel_id: &Uuid, connection_id: &Uuid) {
+        if let Some(channels) = self.subscriptions.get_mut(connection_id) {
+            channels.remove(channel_id);
+            if channels.is_empty() {
+                self.subscriptions.remove(connection_id);
+            }
+        }
+    }
+}
+
+/// Turno exclusivo de publicación en un canal, obtenido con `AppState::publish_order`
+pub struct PublishOrder {
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// Conexión buscada por id: sus datos de cliente y una entrada por canal suscrito
+#[derive(Debug, Serialize)]
+pub struct ConnectionDetails {
+    pub id: Uuid,
+    pub remote_addr: Option<String>,
+    pub user_agent: Option<String>,
+    pub connected_at: DateTime<Utc>,
+    pub subscriptions: Vec<ConnectionInfo>,
+}
+
+/// Lo que un suscriptor ve de los demás con `presence`; sin dirección ni agente
+#[derive(Debug, Serialize)]
+pub struct PresenceEntry {
+    pub id: Uuid,
+    pub client_id: String,
+    pub connected_at: DateTime<Utc>,
+}
+
+/// Vista serializable de una conexión para los endpoints de administración
+#[derive(Debug, Serialize)]
+pub struct ConnectionInfo {
+    pub id: Uuid,
+    pub channel_id: Uuid,
+    pub client_id: String,
+    pub transport: &'static str,
+    pub format: FrameFormat,
+    pub remote_addr: Option<String>,
+    pub user_agent: Option<String>,
+    pub connected_at: DateTime<Utc>,
+    pub rtt_ms: Option<f64>,
+    pub acked_seq: Option<u64>,
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+}
 
 pub struct AppState {
+    pub config: Config,
     pub db: Arc<Database>,
     pub active_channels: Arc<RwLock<HashMap<Uuid, Channel>>>,
-    pub connections: Arc<Mutex<HashMap<Uuid, Vec<Session>>>>,
+    pub connections: Arc<Mutex<ConnectionRegistry>>,
+    pub last_retention_report: Arc<RwLock<Option<RetentionReport>>>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Mensajes publicados en canales en pausa con política `buffer`, en orden de llegada
+    pub pause_buffers: Arc<Mutex<HashMap<Uuid, VecDeque<BroadcastMessage>>>>,
+    /// Si se necesitan ambos, se bloquea siempre después de `connections`
+    pub replay_logs: Arc<Mutex<HashMap<Uuid, ReplayLog>>>,
+    /// Lock de orden de publicación por canal; ver `AppState::publish_order`
+    pub publish_order: Arc<Mutex<HashMap<Uuid, Arc<Mutex<()>>>>>,
+    /// Secreto HMAC de los tokens de suscriptor
+    pub token_secret: Vec<u8>,
 }
 
 impl AppState {
-    pub async fn new(db_path: &str) -> Result<Self> {
+    pub async fn new(config: &Config) -> Result<Self> {
         // Inicializar base de datos ReDB
-        let db = Database::create(db_path)?;
+        let db = Database::create(&config.db_path)?;
 
         // Crear tablas si no existen
         let write_txn = db.begin_write()?;
         {
             let _ = write_txn.open_table(CHANNELS_TABLE)?;
             let _ = write_txn.open_table(MESSAGES_TABLE)?;
+            let _ = write_txn.open_table(CHANNEL_MESSAGES_TABLE)?;
+            let _ = write_txn.open_table(API_KEYS_TABLE)?;
+            let _ = write_txn.open_table(CHANNEL_HISTORY_TABLE)?;
+            let _ = write_txn.open_table(CHANNEL_SEQ_TABLE)?;
+            let _ = write_txn.open_table(MESSAGE_PAYLOADS_TABLE)?;
+            let _ = write_txn.open_table(CHANNEL_SEQ_COUNTERS_TABLE)?;
         }
         write_txn.commit()?;
 
         let state = Self {
+            config: config.clone(),
             db: Arc::new(db),
             active_channels: Arc::new(RwLock::new(HashMap::new())),
-            connections: Arc::new(Mutex::new(HashMap::new())),
+            connections: Arc::new(Mutex::new(ConnectionRegistry::default())),
+            last_retention_report: Arc::new(RwLock::new(None)),
+            rate_limiter: Arc::new(RateLimiter::default()),
+            pause_buffers: Arc::new(Mutex::new(HashMap::new())),
+            replay_logs: Arc::new(Mutex::new(HashMap::new())),
+            publish_order: Arc::new(Mutex::new(HashMap::new())),
+            token_secret: Self::token_secret(config),
         };
 
         // Cargar canales activos desde la base de datos
         state.load_active_channels().await?;
+        state.rebuild_message_index()?;
 
         Ok(state)
     }
 
+    /// Reconstruye el índice por canal si existen mensajes guardados antes de que existiera
+    fn rebuild_message_index(&self) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        let indexed = {
+            let messages = write_txn.open_table(MESSAGES_TABLE)?;
+            let mut index = write_txn.open_table(CHANNEL_MESSAGES_TABLE)?;
+
+            if !index.is_empty()? || messages.is_empty()? {
+                0
+            } else {
+                let mut count = 0;
+                for result in messages.iter()? {
+                    let (_, value) = result?;
+                    let message: BroadcastMessage = serde_json::from_str(value.value())?;
+                    let key = message_index_key(&message.channel_id, &message.timestamp, &message.id);
+                    index.insert(key.as_str(), message.id.to_string().as_str())?;
+                    count += 1;
+                }
+                count
+            }
+        };
+        write_txn.commit()?;
+
+        if indexed > 0 {
+            tracing::info!("Rebuilt channel message index with {} messages", indexed);
+        }
+        Ok(())
+    }
+
+    /// Sin autenticación los tokens no se exigen y basta un secreto efímero;
+    /// con autenticación `Config::validate` obliga a configurarlo
+    fn token_secret(config: &Config) -> Vec<u8> {
+        match &config.auth.token_secret {
+            Some(secret) => secret.as_bytes().to_vec(),
+            None => [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat(),
+        }
+    }
+
     async fn load_active_channels(&self) -> Result<()> {
         let read_txn = self.db.begin_read()?;
         let table = read_txn.open_table(CHANNELS_TABLE)?;
@@ -51,13 +651,13 @@ impl AppState {
             let (_, value) = result?;
             let channel: Channel = serde_json::from_str(value.value())?;
 
-            // Solo cargar canales que estaban activos o pausados
-            if matches!(channel.status, crate::models::channel::ChannelStatus::Active | crate::models::channel::ChannelStatus::Paused) {
+            // Los canales detenidos o archivados quedan en redb hasta que se restauren
+            if channel.status.is_live() {
                 active_channels.insert(channel.id, channel);
             }
         }
 
-        tracing::info!("Loaded {} active channels from database", active_channels.len());
+        tracing::info!("Loaded {} live channels from database", active_channels.len());
         Ok(())
     }
 
@@ -65,7 +665,7 @@ impl AppState {
         let write_txn = self.db.begin_write()?;
         {
             let mut table = write_txn.open_table(CHANNELS_TABLE)?;
-            let channel_json = serde_json::to_string(channel)?;
+            let channel_json = stored_channel_json(channel)?;
             table.insert(channel.id.to_string().as_str(), channel_json.as_str())?;
         }
         write_txn.commit()?;
@@ -76,59 +676,1342 @@ impl AppState {
         let write_txn = self.db.begin_write()?;
         {
             let mut table = write_txn.open_table(MESSAGES_TABLE)?;
-            let message_json = serde_json::to_string(message)?;
-            table.insert(message.id.to_string().as_str(), message_json.as_str())?;
+            let mut index = write_txn.open_table(CHANNEL_MESSAGES_TABLE)?;
+            let message_id = message.id.to_string();
+
+            // El contenido binario se guarda en bruto en su propia tabla, no en base64 dentro del JSON
+            let message_json = match &message.payload {
+                Some(payload) => {
+                    let mut payloads = write_txn.open_table(MESSAGE_PAYLOADS_TABLE)?;
+                    payloads.insert(message_id.as_str(), payload.as_slice())?;
+                    serde_json::to_string(&message.without_payload())?
+                }
+                None => serde_json::to_string(message)?,
+            };
+            table.insert(message_id.as_str(), message_json.as_str())?;
+
+            let key = message_index_key(&message.channel_id, &message.timestamp, &message.id);
+            index.insert(key.as_str(), message_id.as_str())?;
+
+            if message.seq > 0 {
+                let mut seq_index = write_txn.open_table(CHANNEL_SEQ_TABLE)?;
+                let seq_key = seq_index_key(&message.channel_id, message.seq);
+                seq_index.insert(seq_key.as_str(), message_id.as_str())?;
+            }
         }
         write_txn.commit()?;
         Ok(())
     }
 
-    pub async fn get_channel(&self, channel_id: &Uuid) -> Option<Channel> {
-        self.active_channels.read().await.get(channel_id).cloned()
+    /// Reserva las secuencias de un canal hasta `seq`, para que la numeración nunca
+    /// retroceda tras un reinicio y los cursores de los clientes sigan valiendo
+    pub fn save_channel_seq(&self, channel_id: &Uuid, seq: u64) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut counters = write_txn.open_table(CHANNEL_SEQ_COUNTERS_TABLE)?;
+            counters.insert(channel_id.to_string().as_str(), seq)?;
+        }
+        write_txn.commit()?;
+        Ok(())
     }
 
-    pub async fn update_channel_status(&self, channel_id: Uuid, status: crate::models::channel::ChannelStatus) -> Result<()> {
-        let mut active_channels = self.active_channels.write().await;
+    /// Mensajes persistidos de un canal con secuencia mayor que `after_seq`, en orden
+    pub fn messages_after_seq(&self, channel_id: &Uuid, after_seq: u64, limit: usize) -> Result<Vec<BroadcastMessage>> {
+        let start = seq_index_key(channel_id, after_seq.saturating_add(1));
+        let end = format!("{}/~", channel_id);
 
-        if let Some(channel) = active_channels.get_mut(&channel_id) {
-            channel.status = status;
-            channel.updated_at = chrono::Utc::now();
-            self.save_channel(channel).await?;
+        let read_txn = self.db.begin_read()?;
+        let seq_index = read_txn.open_table(CHANNEL_SEQ_TABLE)?;
+        let messages_table = read_txn.open_table(MESSAGES_TABLE)?;
+        let payloads = read_txn.open_table(MESSAGE_PAYLOADS_TABLE)?;
+
+        let mut messages = Vec::new();
+        for result in seq_index.range::<&str>(start.as_str()..end.as_str())?.take(limit) {
+            let (_, value) = result?;
+            if let Some(message) = load_message(&messages_table, &payloads, value.value())? {
+                messages.push(message);
+            }
         }
 
-        Ok(())
+        Ok(messages)
     }
 
-    pub async fn add_connection(&self, channel_id: Uuid, session: Session) {
-        let mut connections = self.connections.lock().await;
-        connections.entry(channel_id).or_default().push(session);
+    /// Última secuencia reservada o persistida de un canal, para continuar la numeración tras reiniciar
+    fn last_stored_seq(&self, channel_id: &Uuid) -> Result<u64> {
+        let start = format!("{}/", channel_id);
+        let end = format!("{}/~", channel_id);
+
+        let read_txn = self.db.begin_read()?;
+        let counters = read_txn.open_table(CHANNEL_SEQ_COUNTERS_TABLE)?;
+        let seq_index = read_txn.open_table(CHANNEL_SEQ_TABLE)?;
+
+        // Las bases de datos anteriores al contador solo tienen el índice por secuencia
+        let counter = counters.get(channel_id.to_string().as_str())?.map_or(0, |seq| seq.value());
+        let last = seq_index.range::<&str>(start.as_str()..end.as_str())?.next_back().transpose()?;
+        let indexed = last
+            .and_then(|(key, _)| key.value().rsplit('/').next().and_then(|seq| seq.parse().ok()))
+            .unwrap_or(0);
+
+        Ok(counter.max(indexed))
     }
 
-    pub async fn remove_connection(&self, channel_id: &Uuid, session: &Session) {
-        let mut connections = self.connections.lock().await;
-        if let Some(sessions) = connections.get_mut(channel_id) {
-            sessions.retain(|s| !std::ptr::eq(s, session));
+    /// Lee mensajes persistidos de un canal en orden cronológico usando el índice por canal
+    pub async fn get_channel_messages(
+        &self,
+        channel_id: &Uuid,
+        query: &MessageHistoryQuery,
+    ) -> Result<MessageHistoryPage> {
+        let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT).clamp(1, MAX_HISTORY_LIMIT);
+        let prefix = format!("{}/", channel_id);
+
+        let mut start = match &query.since {
+            Some(since) => message_index_bound(channel_id, since),
+            None => prefix.clone(),
+        };
+        let mut start_inclusive = true;
+
+        if let Some(cursor) = &query.cursor {
+            if !cursor.starts_with(&prefix) {
+                anyhow::bail!("Invalid cursor for channel {}", channel_id);
+            }
+            if *cursor >= start {
+                start = cursor.clone();
+                start_inclusive = false;
+            }
+        }
+
+        // `~` ordena después de cualquier dígito o uuid, cerrando el rango del canal
+        let end = match &query.until {
+            Some(until) => format!("{}/~", message_index_bound(channel_id, until)),
+            None => format!("{}~", prefix),
+        };
+
+        let start_bound = if start_inclusive {
+            Bound::Included(start.as_str())
+        } else {
+            Bound::Excluded(start.as_str())
+        };
+
+        let read_txn = self.db.begin_read()?;
+        let index = read_txn.open_table(CHANNEL_MESSAGES_TABLE)?;
+        let messages_table = read_txn.open_table(MESSAGES_TABLE)?;
+        let payloads = read_txn.open_table(MESSAGE_PAYLOADS_TABLE)?;
+
+        let mut messages = Vec::with_capacity(limit);
+        let mut last_key = None;
+        let mut has_more = false;
+
+        for result in index.range::<&str>((start_bound, Bound::Excluded(end.as_str())))? {
+            let (key, value) = result?;
+
+            if messages.len() == limit {
+                has_more = true;
+                break;
+            }
+
+            if let Some(message) = load_message(&messages_table, &payloads, value.value())? {
+                messages.push(message);
+            }
+            last_key = Some(key.value().to_string());
         }
+
+        Ok(MessageHistoryPage {
+            messages,
+            next_cursor: if has_more { last_key } else { None },
+        })
     }
 
-    pub async fn broadcast_to_channel(&self, channel_id: &Uuid, message: &str) -> Result<usize> {
-        let mut connections = self.connections.lock().await;
-        let mut sent_count = 0;
+    /// Elimina mensajes anteriores a `cutoff` y recorta cada canal a `max_per_channel`,
+    /// conservando los más recientes. Recorre el índice canal a canal por rangos en una
+    /// sola transacción; solo se cargan en memoria las claves que se van a borrar.
+    pub async fn purge_messages(&self, cutoff: DateTime<Utc>, max_per_channel: usize) -> Result<PurgeStats> {
+        let mut stats = PurgeStats::default();
 
-        if let Some(sessions) = connections.get_mut(channel_id) {
-            let mut active_sessions = Vec::new();
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut messages = write_txn.open_table(MESSAGES_TABLE)?;
+            let mut index = write_txn.open_table(CHANNEL_MESSAGES_TABLE)?;
+            let mut seq_index = write_txn.open_table(CHANNEL_SEQ_TABLE)?;
+            let mut payloads = write_txn.open_table(MESSAGE_PAYLOADS_TABLE)?;
 
-            for session in sessions.iter_mut() {
-                if session.text(message).await.is_ok() {
-                    sent_count += 1;
-                    active_sessions.push(session.clone());
+            // Primera clave del índice y, después de cada canal, la primera del siguiente
+            let mut next_key = index.first()?.map(|(key, _)| key.value().to_string());
+
+            while let Some(key) = next_key {
+                let Some(channel_id) = key.split('/').next().and_then(|id| Uuid::parse_str(id).ok()) else {
+                    anyhow::bail!("Invalid channel message index key '{}'", key);
+                };
+                let start = format!("{}/", channel_id);
+                let end = format!("{}/~", channel_id);
+                let cutoff_bound = message_index_bound(&channel_id, &cutoff);
+
+                let mut expired = Vec::new();
+                for result in index.range::<&str>(start.as_str()..cutoff_bound.as_str())? {
+                    let (key, value) = result?;
+                    expired.push((key.value().to_string(), value.value().to_string()));
+                }
+
+                // Los más recientes se conservan; se cuenta desde el final del rango
+                let mut excess = Vec::new();
+                for result in index.range::<&str>(cutoff_bound.as_str()..end.as_str())?.rev().skip(max_per_channel) {
+                    let (key, value) = result?;
+                    excess.push((key.value().to_string(), value.value().to_string()));
+                }
+
+                for (key, message_id) in expired.iter().chain(&excess) {
+                    index.remove(key.as_str())?;
+                    remove_message(&mut messages, &mut seq_index, &mut payloads, message_id)?;
+                }
+
+                stats.expired_removed += expired.len();
+                if !excess.is_empty() {
+                    stats.trimmed_removed += excess.len();
+                    stats.channels_trimmed += 1;
                 }
+
+                next_key = index
+                    .range::<&str>((Bound::Excluded(end.as_str()), Bound::Unbounded))?
+                    .next()
+                    .transpose()?
+                    .map(|(key, _)| key.value().to_string());
             }
+        }
+        write_txn.commit()?;
+
+        Ok(stats)
+    }
+
+    pub async fn save_api_key(&self, key_hash: &str, api_key: &ApiKey) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(API_KEYS_TABLE)?;
+            let api_key_json = serde_json::to_string(api_key)?;
+            table.insert(key_hash, api_key_json.as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    pub async fn get_api_key(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(API_KEYS_TABLE)?;
 
-            *sessions = active_sessions;
+        match table.get(key_hash)? {
+            Some(value) => Ok(Some(serde_json::from_str(value.value())?)),
+            None => Ok(None),
         }
+    }
 
-        Ok(sent_count)
+    /// Busca una API key por id; devuelve también su hash para poder actualizarla
+    pub async fn find_api_key_by_id(&self, key_id: &Uuid) -> Result<Option<(String, ApiKey)>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(API_KEYS_TABLE)?;
+
+        for result in table.iter()? {
+            let (key, value) = result?;
+            let api_key: ApiKey = serde_json::from_str(value.value())?;
+            if api_key.id == *key_id {
+                return Ok(Some((key.value().to_string(), api_key)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(API_KEYS_TABLE)?;
+
+        let mut api_keys = Vec::new();
+        for result in table.iter()? {
+            let (_, value) = result?;
+            api_keys.push(serde_json::from_str::<ApiKey>(value.value())?);
+        }
+
+        api_keys.sort_by_key(|api_key| api_key.created_at);
+        Ok(api_keys)
     }
-    
-}
\ No newline at end of file
+
+    /// Lee un canal directamente de la base de datos, esté o no cargado en memoria
+    pub async fn load_channel(&self, channel_id: &Uuid) -> Result<Option<Channel>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(CHANNELS_TABLE)?;
+
+        match table.get(channel_id.to_string().as_str())? {
+            Some(value) => Ok(Some(serde_json::from_str(value.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Todos los canales guardados en redb, incluidos los que no están en memoria
+    pub async fn load_stored_channels(&self) -> Result<Vec<Channel>> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(CHANNELS_TABLE)?;
+
+        let mut channels = Vec::new();
+        for result in table.iter()? {
+            let (_, value) = result?;
+            channels.push(serde_json::from_str(value.value())?);
+        }
+
+        Ok(channels)
+    }
+
+    /// Retira un canal de redb en una sola transacción junto con su transición final.
+    /// Un canal `Archived` se guarda; uno `Deleted` se borra conservando su historial.
+    /// `purge` elimina además todos sus mensajes.
+    pub async fn delete_channel_record(
+        &self,
+        channel: &Channel,
+        transition: &ChannelTransition,
+        purge: bool,
+    ) -> Result<usize> {
+        let channel_id = &channel.id;
+        let start = format!("{}/", channel_id);
+        let end = format!("{}/~", channel_id);
+
+        let write_txn = self.db.begin_write()?;
+        let purged = {
+            let mut channels = write_txn.open_table(CHANNELS_TABLE)?;
+            if channel.status == ChannelStatus::Deleted {
+                channels.remove(channel_id.to_string().as_str())?;
+                let mut counters = write_txn.open_table(CHANNEL_SEQ_COUNTERS_TABLE)?;
+                counters.remove(channel_id.to_string().as_str())?;
+            } else {
+                let channel_json = stored_channel_json(channel)?;
+                channels.insert(channel_id.to_string().as_str(), channel_json.as_str())?;
+            }
+
+            let mut history = write_txn.open_table(CHANNEL_HISTORY_TABLE)?;
+            let key = message_index_key(channel_id, &transition.at, &Uuid::new_v4());
+            let transition_json = serde_json::to_string(transition)?;
+            history.insert(key.as_str(), transition_json.as_str())?;
+
+            if purge {
+                let mut messages = write_txn.open_table(MESSAGES_TABLE)?;
+                let mut index = write_txn.open_table(CHANNEL_MESSAGES_TABLE)?;
+                let mut seq_index = write_txn.open_table(CHANNEL_SEQ_TABLE)?;
+                let mut payloads = write_txn.open_table(MESSAGE_PAYLOADS_TABLE)?;
+
+                let mut entries = Vec::new();
+                for result in index.range::<&str>(start.as_str()..end.as_str())? {
+                    let (key, value) = result?;
+                    entries.push((key.value().to_string(), value.value().to_string()));
+                }
+
+                for (key, message_id) in &entries {
+                    index.remove(key.as_str())?;
+                    remove_message(&mut messages, &mut seq_index, &mut payloads, message_id)?;
+                }
+
+                entries.len()
+            } else {
+                0
+            }
+        };
+        write_txn.commit()?;
+
+        Ok(purged)
+    }
+
+    /// Vacía el buffer de pausa de un canal devolviendo los mensajes en orden de llegada
+    pub async fn take_pause_buffer(&self, channel_id: &Uuid) -> VecDeque<BroadcastMessage> {
+        self.pause_buffers.lock().await.remove(channel_id).unwrap_or_default()
+    }
+
+    pub async fn get_channel(&self, channel_id: &Uuid) -> Option<Channel> {
+        self.active_channels.read().await.get(channel_id).cloned()
+    }
+
+    /// Cambia el estado de un canal cargado si la transición es válida y la registra en su historial
+    pub async fn transition_channel(
+        &self,
+        channel_id: Uuid,
+        status: ChannelStatus,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<(Channel, ChannelTransition)> {
+        let mut active_channels = self.active_channels.write().await;
+        let Some(channel) = active_channels.get_mut(&channel_id) else {
+            return Err(self.unloaded_transition_error(&channel_id, status).await);
+        };
+
+        let transition = Self::apply_transition(channel, status, actor, reason)?;
+        self.save_channel_transition(channel, &transition).await?;
+        Ok((channel.clone(), transition))
+    }
+
+    /// Error para una transición sobre un canal que no está en memoria. Fuera de memoria
+    /// solo quedan canales detenidos o archivados, que antes deben restaurarse.
+    pub async fn unloaded_transition_error(&self, channel_id: &Uuid, status: ChannelStatus) -> anyhow::Error {
+        match self.load_channel(channel_id).await {
+            Ok(Some(stored)) => AppError::InvalidTransition { from: stored.status, to: status }.into(),
+            Ok(None) => AppError::ChannelNotFound.into(),
+            Err(e) => e,
+        }
+    }
+
+    /// Valida y aplica la transición sobre `channel` sin persistirla
+    pub fn apply_transition(
+        channel: &mut Channel,
+        status: ChannelStatus,
+        actor: &str,
+        reason: Option<String>,
+    ) -> Result<ChannelTransition> {
+        if !channel.status.can_transition_to(status) {
+            return Err(AppError::InvalidTransition { from: channel.status, to: status }.into());
+        }
+
+        let transition = ChannelTransition {
+            from: channel.status,
+            to: status,
+            at: Utc::now(),
+            actor: actor.to_string(),
+            reason,
+        };
+
+        channel.status = status;
+        channel.updated_at = transition.at;
+        // Al salir de la pausa el buffer se entrega o se descarta; el servicio se encarga
+        if transition.from == ChannelStatus::Paused {
+            channel.buffered = BufferDepth::default();
+        }
+        Ok(transition)
+    }
+
+    /// Guarda el canal y su transición en la misma transacción
+    pub async fn save_channel_transition(&self, channel: &Channel, transition: &ChannelTransition) -> Result<()> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut channels = write_txn.open_table(CHANNELS_TABLE)?;
+            let channel_json = stored_channel_json(channel)?;
+            channels.insert(channel.id.to_string().as_str(), channel_json.as_str())?;
+
+            let mut history = write_txn.open_table(CHANNEL_HISTORY_TABLE)?;
+            let key = message_index_key(&channel.id, &transition.at, &Uuid::new_v4());
+            let transition_json = serde_json::to_string(transition)?;
+            history.insert(key.as_str(), transition_json.as_str())?;
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+
+    /// Historial de transiciones de un canal en orden cronológico
+    pub async fn get_channel_history(&self, channel_id: &Uuid) -> Result<Vec<ChannelTransition>> {
+        let start = format!("{}/", channel_id);
+        let end = format!("{}/~", channel_id);
+
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(CHANNEL_HISTORY_TABLE)?;
+
+        let mut transitions = Vec::new();
+        for result in table.range::<&str>(start.as_str()..end.as_str())? {
+            let (_, value) = result?;
+            transitions.push(serde_json::from_str(value.value())?);
+        }
+
+        Ok(transitions)
+    }
+
+    /// Límite efectivo de conexiones de un canal: la configuración global actúa como techo
+    pub fn max_connections_for(&self, channel: &Channel) -> usize {
+        channel.settings.max_connections.min(self.config.max_connections_per_channel)
+    }
+
+    /// Tamaño máximo de mensaje de un canal: el override del canal solo puede reducir el global
+    pub fn message_size_limit_for(&self, channel: &Channel) -> usize {
+        channel
+            .settings
+            .message_size_limit
+            .map_or(self.config.message_size_limit, |limit| limit.min(self.config.message_size_limit))
+    }
+
+    /// Registra la conexión si el canal no ha alcanzado `limit`.
+    /// Devuelve el número de conexiones actuales cuando se rechaza.
+    pub async fn try_add_connection(&self, channel_id: Uuid, connection: Connection, limit: usize) -> Result<(), usize> {
+        let mut connections = self.connections.lock().await;
+        let current = connections.count(&channel_id);
+
+        if current >= limit {
+            return Err(current);
+        }
+
+        connections.insert(channel_id, connection);
+        Ok(())
+    }
+
+    pub async fn remove_connection(&self, channel_id: &Uuid, connection_id: &Uuid) -> Option<Connection> {
+        self.connections.lock().await.remove(channel_id, connection_id)
+    }
+
+    /// Registra una conexión del hub al aceptarla, para encontrarla por id antes de su
+    /// primera suscripción. `remove_client` la da de baja.
+    pub async fn register_client(&self, connection_id: Uuid, meta: ClientMeta) {
+        let client = AcceptedClient { meta, connected_at: Utc::now() };
+        self.connections.lock().await.clients.insert(connection_id, client);
+    }
+
+    /// Elimina una conexión de todos sus canales. Devuelve a cuántos estaba suscrita.
+    /// Solo aquí, al cerrarse el socket, se olvida su límite de publicación: darse de baja
+    /// y volver a suscribirse no lo reinicia.
+    pub async fn remove_client(&self, connection_id: &Uuid) -> usize {
+        self.rate_limiter.remove(&RateLimitKey::Connection(*connection_id));
+
+        let mut connections = self.connections.lock().await;
+        connections.clients.remove(connection_id);
+        let channels = connections.subscriptions(connection_id);
+        for channel_id in &channels {
+            connections.remove(channel_id, connection_id);
+        }
+
+        channels.len()
+    }
+
+    pub async fn subscriptions(&self, connection_id: &Uuid) -> Vec<Uuid> {
+        self.connections.lock().await.subscriptions(connection_id)
+    }
+
+    pub async fn is_subscribed(&self, channel_id: &Uuid, connection_id: &Uuid) -> bool {
+        self.connections
+            .lock()
+            .await
+            .channel(channel_id)
+            .is_some_and(|sessions| sessions.contains_key(connection_id))
+    }
+
+    /// Guarda el último round-trip en todas las suscripciones de la conexión
+    pub async fn record_rtt(&self, connection_id: &Uuid, rtt_ms: f64) {
+        let mut connections = self.connections.lock().await;
+        for channel_id in connections.subscriptions(connection_id) {
+            if let Some(connection) = connections.get_mut(&channel_id, connection_id) {
+                connection.rtt_ms = Some(rtt_ms);
+            }
+        }
+    }
+
+    /// Registra el `ack` de una conexión; devuelve `false` si no está suscrita al canal
+    pub async fn record_ack(&self, channel_id: &Uuid, connection_id: &Uuid, seq: u64) -> bool {
+        let mut connections = self.connections.lock().await;
+        match connections.get_mut(channel_id, connection_id) {
+            Some(connection) => {
+                connection.acked_seq = Some(connection.acked_seq.map_or(seq, |acked| acked.max(seq)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub async fn list_connections(&self, channel_id: &Uuid) -> Vec<ConnectionInfo> {
+        let connections = self.connections.lock().await;
+        connections
+            .channel(channel_id)
+            .map(|sessions| sessions.values().map(|connection| connection.info(*channel_id)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Quién está conectado a un canal, sin los datos de red de cada cliente
+    pub async fn list_presence(&self, channel_id: &Uuid) -> Vec<PresenceEntry> {
+        let connections = self.connections.lock().await;
+        connections
+            .channel(channel_id)
+            .map(|sessions| {
+                sessions
+                    .values()
+                    .map(|connection| PresenceEntry {
+                        id: connection.id,
+                        client_id: connection.client_id.clone(),
+                        connected_at: connection.connected_at,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Conexión por id con una entrada por canal suscrito (varias solo en el hub). Las del
+    /// hub se encuentran desde que se aceptan; las demás solo existen mientras están suscritas.
+    pub async fn find_connection(&self, connection_id: &Uuid) -> Option<ConnectionDetails> {
+        let connections = self.connections.lock().await;
+        let mut subscriptions: Vec<ConnectionInfo> = connections
+            .subscriptions(connection_id)
+            .into_iter()
+            .filter_map(|channel_id| {
+                connections
+                    .channel(&channel_id)
+                    .and_then(|sessions| sessions.get(connection_id))
+                    .map(|connection| connection.info(channel_id))
+            })
+            .collect();
+        subscriptions.sort_by_key(|info| info.connected_at);
+
+        let (meta, connected_at) = match connections.clients.get(connection_id) {
+            Some(client) => (client.meta.clone(), client.connected_at),
+            None => {
+                let first = subscriptions.first()?;
+                let meta = ClientMeta { remote_addr: first.remote_addr.clone(), user_agent: first.user_agent.clone() };
+                (meta, first.connected_at)
+            }
+        };
+
+        Some(ConnectionDetails {
+            id: *connection_id,
+            remote_addr: meta.remote_addr,
+            user_agent: meta.user_agent,
+            connected_at,
+            subscriptions,
+        })
+    }
+
+    /// Cierra las conexiones más recientes que excedan `limit`. Devuelve cuántas se cerraron.
+    pub async fn enforce_connection_limit(&self, channel_id: &Uuid, limit: usize) -> usize {
+        let mut connections = self.connections.lock().await;
+        let Some(sessions) = connections.channel(channel_id) else {
+            return 0;
+        };
+
+        if sessions.len() <= limit {
+            return 0;
+        }
+
+        let mut by_age: Vec<_> = sessions
+            .values()
+            .map(|connection| (connection.connected_at, connection.id))
+            .collect();
+        by_age.sort();
+
+        let excess: Vec<Uuid> = by_age.into_iter().skip(limit).map(|(_, id)| id).collect();
+        for connection_id in &excess {
+            if let Some(connection) = connections.remove(channel_id, connection_id) {
+                connection.end(
+                    *channel_id,
+                    CloseReason {
+                        code: CloseCode::Policy,
+                        description: Some("channel connection limit lowered".to_string()),
+                    },
+                );
+            }
+        }
+
+        excess.len()
+    }
+
+    /// Cierra con código normal y elimina del registro todas las conexiones de un canal.
+    /// `reason` viaja en el frame de cierre para que el cliente no intente reconectar.
+    pub async fn close_channel_connections(&self, channel_id: &Uuid, reason: &str) -> usize {
+        let mut connections = self.connections.lock().await;
+        let sessions = connections.remove_channel(channel_id);
+        let closed = sessions.len();
+
+        for connection in sessions {
+            connection.end(
+                *channel_id,
+                CloseReason {
+                    code: CloseCode::Normal,
+                    description: Some(reason.to_string()),
+                },
+            );
+        }
+
+        closed
+    }
+
+    pub async fn broadcast_to_channel(&self, channel_id: &Uuid, response: &WebSocketResponse) -> Result<usize> {
+        let mut connections = self.connections.lock().await;
+        let outgoing = Outgoing::Control(response);
+        Self::send_to_sessions(&mut connections, channel_id, &outgoing)
+    }
+
+    /// Encola un frame en las sesiones en vivo del canal, sea cual sea su transporte, y
+    /// elimina las que fallan. No espera a ningún cliente: se llama con `connections` bloqueado.
+    fn send_to_sessions(
+        connections: &mut ConnectionRegistry,
+        channel_id: &Uuid,
+        outgoing: &Outgoing<'_>,
+    ) -> Result<usize> {
+        let mut sent_count = 0;
+        let mut closed = Vec::new();
+
+        if let Some(sessions) = connections.channels.get_mut(channel_id) {
+            let mut rendered: HashMap<FrameFormat, Frame> = HashMap::new();
+
+            for connection in sessions.values_mut().filter(|connection| !connection.replaying) {
+                let frame = match rendered.entry(connection.format) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(match outgoing {
+                        Outgoing::Control(response) => Frame::json(response.to_frame(connection.format)?),
+                        Outgoing::Message { message, channel_name } => {
+                            Frame::message(message, channel_name, connection.format)?
+                        }
+                    }),
+                };
+
+                if connection.sink.offer(frame).is_ok() {
+                    connection.messages_sent += 1;
+                    connection.bytes_sent += frame.size() as u64;
+                    sent_count += 1;
+                } else {
+                    closed.push(connection.id);
+                }
+            }
+        }
+
+        // Desconectados o demasiado lentos: se cierra el transporte entero, también en el
+        // hub, porque una cola llena deja sin entrega a todos sus canales
+        for connection_id in closed {
+            if let Some(connection) = connections.remove(channel_id, &connection_id) {
+                tracing::debug!("Dropping connection {} from channel {}: delivery failed", connection_id, channel_id);
+                connection.sink.close(CloseReason {
+                    code: CloseCode::Policy,
+                    description: Some("client is not keeping up".to_string()),
+                });
+            }
+        }
+
+        Ok(sent_count)
+    }
+
+    /// Log de reenvío del canal; la secuencia continúa desde redb la primera vez
+    fn replay_log<'a>(&self, logs: &'a mut HashMap<Uuid, ReplayLog>, channel_id: &Uuid) -> Result<&'a mut ReplayLog> {
+        if !logs.contains_key(channel_id) {
+            let last_seq = self.last_stored_seq(channel_id)?;
+            logs.insert(*channel_id, ReplayLog { last_seq, reserved_seq: last_seq, recent: VecDeque::new() });
+        }
+
+        Ok(logs.get_mut(channel_id).expect("replay log inserted above"))
+    }
+
+    /// Última secuencia asignada en un canal
+    pub async fn last_seq(&self, channel_id: &Uuid) -> Result<u64> {
+        let mut logs = self.replay_logs.lock().await;
+        Ok(self.replay_log(&mut logs, channel_id)?.last_seq)
+    }
+
+    /// Toma el lock de orden de publicación de un canal. Mientras se mantiene nadie más
+    /// numera ni entrega mensajes en ese canal. Se toma antes que cualquier otro lock.
+    pub async fn publish_order(&self, channel_id: &Uuid) -> PublishOrder {
+        let lock = self.publish_order.lock().await.entry(*channel_id).or_default().clone();
+        PublishOrder { _guard: lock.lock_owned().await }
+    }
+
+    /// Publica un mensaje respetando el orden del canal; ver `publish_ordered`
+    pub async fn publish(&self, channel: &Channel, message: BroadcastMessage) -> Result<(BroadcastMessage, usize)> {
+        let order = self.publish_order(&channel.id).await;
+        self.publish_ordered(&order, channel, message).await
+    }
+
+    /// Asigna la siguiente secuencia, la persiste junto con el mensaje si el canal lo pide y
+    /// entrega a las sesiones. El turno de `_order` garantiza que la entrega sigue el orden
+    /// de secuencia; el lock de conexiones solo se toma para el envío, no durante redb.
+    pub async fn publish_ordered(
+        &self,
+        _order: &PublishOrder,
+        channel: &Channel,
+        mut message: BroadcastMessage,
+    ) -> Result<(BroadcastMessage, usize)> {
+        // Un borrado o una parada que tomó el turno antes ya retiró el canal: no se numera
+        // ni se guarda nada, o quedarían filas huérfanas de un canal que ya no existe
+        match self.active_channels.read().await.get(&channel.id).map(|current| current.status) {
+            Some(ChannelStatus::Active | ChannelStatus::Paused) => {}
+            Some(_) => anyhow::bail!("Channel is not active"),
+            None => return Err(AppError::ChannelNotFound.into()),
+        }
+
+        let reserved_seq = {
+            let mut logs = self.replay_logs.lock().await;
+            let log = self.replay_log(&mut logs, &channel.id)?;
+            message.seq = log.last_seq + 1;
+            log.reserved_seq
+        };
+
+        // Solo se escribe el contador al agotar el bloque reservado
+        let reserved_seq = if message.seq > reserved_seq {
+            let reserved_seq = message.seq + SEQ_RESERVATION_BLOCK - 1;
+            self.save_channel_seq(&channel.id, reserved_seq)?;
+            reserved_seq
+        } else {
+            reserved_seq
+        };
+
+        if channel.settings.persist_messages {
+            self.save_message(&message).await?;
+        }
+
+        {
+            let mut logs = self.replay_logs.lock().await;
+            let log = self.replay_log(&mut logs, &channel.id)?;
+            log.last_seq = message.seq;
+            log.reserved_seq = reserved_seq;
+            let capacity = self.config.websocket.replay_buffer_size;
+            if capacity > 0 {
+                log.recent.push_back(message.clone());
+                while log.recent.len() > capacity {
+                    log.recent.pop_front();
+                }
+            }
+        }
+
+        let mut connections = self.connections.lock().await;
+        let outgoing = Outgoing::Message { message: &message, channel_name: &channel.name };
+        let sent_count = Self::send_to_sessions(&mut connections, &channel.id, &outgoing)?;
+
+        Ok((message, sent_count))
+    }
+
+    /// Reenvía a una conexión en modo `replaying` los mensajes posteriores a `since_seq` y
+    /// después la activa para el tráfico en vivo. Los huecos que ya no están en memoria ni
+    /// en redb se anuncian con un frame `gap`. Devuelve cuántos mensajes se reenviaron.
+    pub async fn replay_missed(
+        &self,
+        channel: &Channel,
+        connection_id: &Uuid,
+        sink: &mut Sink,
+        format: FrameFormat,
+        since_seq: u64,
+    ) -> Result<usize> {
+        let mut cursor = since_seq;
+        let mut replayed = 0;
+        let mut sent = (0u64, 0u64);
+
+        loop {
+            // Con el turno de publicación nada se entrega entre leer `last_seq` y pasar la
+            // conexión al tráfico en vivo, así que no se pierde ni se duplica ningún mensaje
+            let order = self.publish_order(&channel.id).await;
+            let (recent, last_seq) = {
+                let mut logs = self.replay_logs.lock().await;
+                let log = self.replay_log(&mut logs, &channel.id)?;
+
+                let in_memory = log
+                    .recent
+                    .front()
+                    .is_some_and(|oldest| oldest.seq <= cursor + 1);
+                let recent = (in_memory || !channel.settings.persist_messages).then(|| {
+                    log.recent
+                        .iter()
+                        .filter(|message| message.seq > cursor)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                });
+
+                (recent, log.last_seq)
+            };
+
+            if cursor >= last_seq {
+                // La secuencia se guarda en redb y nunca retrocede: un cursor por delante
+                // no viene de este servidor y se avisa con un hueco
+                if cursor > last_seq {
+                    let gap = gap_frame(channel.id, cursor, last_seq, None).to_frame(format)?;
+                    sink.send(&gap, Some(last_seq)).await?;
+                    sent = (sent.0 + 1, sent.1 + gap.len() as u64);
+                }
+
+                // Sin mensajes pendientes: a partir de aquí la conexión recibe el tráfico en vivo
+                let mut connections = self.connections.lock().await;
+                if let Some(connection) = connections.get_mut(&channel.id, connection_id) {
+                    connection.replaying = false;
+                    connection.messages_sent += sent.0;
+                    connection.bytes_sent += sent.1;
+                }
+                return Ok(replayed);
+            }
+            drop(order);
+
+            let batch = match recent {
+                Some(batch) => batch,
+                None => self.messages_after_seq(&channel.id, cursor, MAX_HISTORY_LIMIT)?,
+            };
+
+            let first_available = batch.first().map(|message| message.seq);
+            if first_available != Some(cursor + 1) {
+                // El hueco lleva como secuencia el punto de reanudación: en SSE es su `id:` y
+                // en long-polling el siguiente cursor, aunque el lote no traiga mensajes
+                let resume_seq = first_available.map_or(last_seq, |seq| seq - 1);
+                let gap = gap_frame(channel.id, cursor, last_seq, first_available).to_frame(format)?;
+                sink.send(&gap, Some(resume_seq)).await?;
+                sent = (sent.0 + 1, sent.1 + gap.len() as u64);
+            }
+
+            match batch.last() {
+                Some(last) => cursor = last.seq,
+                None => cursor = last_seq,
+            }
+
+            for message in &batch {
+                let frame = Frame::message(message, &channel.name, format)?;
+                sink.deliver(&frame).await?;
+                sent = (sent.0 + 1, sent.1 + frame.size() as u64);
+            }
+            replayed += batch.len();
+        }
+    }
+
+    /// Olvida la secuencia en memoria y el lock de orden de un canal eliminado. Se llama con
+    /// el turno del canal tomado; quien aún lo espere no pasará la comprobación de `publish_ordered`.
+    pub async fn drop_replay_log(&self, channel_id: &Uuid) {
+        self.replay_logs.lock().await.remove(channel_id);
+        self.publish_order.lock().await.remove(channel_id);
+    }
+}
+
+/// Frame que avisa de mensajes posteriores a `since_seq` que ya no se pueden recuperar
+fn gap_frame(channel_id: Uuid, since_seq: u64, last_seq: u64, first_available_seq: Option<u64>) -> WebSocketResponse {
+    WebSocketResponse {
+        status: "gap".to_string(),
+        message: "Some messages since the requested sequence are no longer available".to_string(),
+        channel_id,
+        timestamp: Utc::now(),
+        seq: None,
+        data: Some(serde_json::json!({
+            "since_seq": since_seq,
+            "first_available_seq": first_available_seq,
+            "last_seq": last_seq
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::channel::ChannelSettings;
+    use crate::utils::test_support::{loaded_channel, test_channel, test_config, test_state, text_message, TempDir};
+    use chrono::Duration;
+
+    fn connection(id: Uuid) -> Connection {
+        let (sender, _) = mpsc::channel(1);
+        Connection::new(id, "client".to_string(), Sink::Poll(sender))
+    }
+
+    #[test]
+    fn test_registry_tracks_subscriptions_per_connection() {
+        let mut registry = ConnectionRegistry::default();
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        let hub = Uuid::new_v4();
+
+        registry.insert(first, connection(hub));
+        registry.insert(second, connection(hub));
+        registry.insert(second, connection(Uuid::new_v4()));
+
+        let mut subscriptions = registry.subscriptions(&hub);
+        subscriptions.sort();
+        let mut expected = vec![first, second];
+        expected.sort();
+        assert_eq!(subscriptions, expected);
+        assert_eq!(registry.count(&second), 2);
+
+        assert!(registry.remove(&first, &hub).is_some());
+        assert_eq!(registry.subscriptions(&hub), vec![second]);
+        assert!(registry.channel(&first).is_none());
+
+        assert_eq!(registry.remove_channel(&second).len(), 2);
+        assert!(registry.subscriptions(&hub).is_empty());
+    }
+
+    fn history_query(cursor: Option<String>, limit: Option<usize>) -> MessageHistoryQuery {
+        MessageHistoryQuery { cursor, since: None, until: None, limit }
+    }
+
+    /// Guarda `count` mensajes separados un segundo a partir de `start`
+    async fn save_messages(state: &AppState, channel_id: Uuid, start: DateTime<Utc>, count: usize) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        for i in 0..count {
+            let message = text_message(channel_id, &format!("m{}", i), start + Duration::seconds(i as i64));
+            state.save_message(&message).await.unwrap();
+            ids.push(message.id);
+        }
+        ids
+    }
+
+    fn ids(page: &MessageHistoryPage) -> Vec<Uuid> {
+        page.messages.iter().map(|message| message.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_history_cursor_pagination() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel_id = Uuid::new_v4();
+        let saved = save_messages(&state, channel_id, Utc::now(), 5).await;
+        // Otro canal no aparece en el rango
+        save_messages(&state, Uuid::new_v4(), Utc::now(), 2).await;
+
+        let first = state.get_channel_messages(&channel_id, &history_query(None, Some(2))).await.unwrap();
+        assert_eq!(ids(&first), saved[..2]);
+        assert!(first.next_cursor.is_some());
+
+        let second = state
+            .get_channel_messages(&channel_id, &history_query(first.next_cursor, Some(2)))
+            .await
+            .unwrap();
+        assert_eq!(ids(&second), saved[2..4]);
+
+        // La última página no devuelve cursor, aunque quede justo un mensaje
+        let last = state
+            .get_channel_messages(&channel_id, &history_query(second.next_cursor, Some(2)))
+            .await
+            .unwrap();
+        assert_eq!(ids(&last), saved[4..]);
+        assert_eq!(last.next_cursor, None);
+
+        // Una página que termina exactamente en el último mensaje tampoco
+        let exact = state.get_channel_messages(&channel_id, &history_query(None, Some(5))).await.unwrap();
+        assert_eq!(ids(&exact), saved);
+        assert_eq!(exact.next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_history_bounds_and_limits() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let channel_id = Uuid::new_v4();
+        let start = Utc::now();
+        let saved = save_messages(&state, channel_id, start, 5).await;
+
+        // `since` y `until` son inclusivos
+        let query = MessageHistoryQuery {
+            cursor: None,
+            since: Some(start + Duration::seconds(1)),
+            until: Some(start + Duration::seconds(3)),
+            limit: None,
+        };
+        let page = state.get_channel_messages(&channel_id, &query).await.unwrap();
+        assert_eq!(ids(&page), saved[1..4]);
+
+        // El límite se ajusta a [1, MAX_HISTORY_LIMIT]
+        let page = state.get_channel_messages(&channel_id, &history_query(None, Some(0))).await.unwrap();
+        assert_eq!(ids(&page), saved[..1]);
+        assert!(page.next_cursor.is_some());
+
+        let page = state.get_channel_messages(&channel_id, &history_query(None, Some(usize::MAX))).await.unwrap();
+        assert_eq!(ids(&page), saved);
+
+        // Un cursor de otro canal se rechaza
+        let foreign = format!("{}/00000000000000000000/x", Uuid::new_v4());
+        assert!(state.get_channel_messages(&channel_id, &history_query(Some(foreign), None)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purge_expires_and_trims_per_channel() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let now = Utc::now();
+        let (busy, quiet) = (Uuid::new_v4(), Uuid::new_v4());
+
+        // `busy`: 2 caducados y 4 vigentes; `quiet`: 1 caducado y 1 vigente
+        let busy_old = save_messages(&state, busy, now - Duration::days(10), 2).await;
+        let busy_recent = save_messages(&state, busy, now, 4).await;
+        save_messages(&state, quiet, now - Duration::days(10), 1).await;
+        let quiet_recent = save_messages(&state, quiet, now, 1).await;
+
+        let stats = state.purge_messages(now - Duration::days(1), 3).await.unwrap();
+        assert_eq!(stats.expired_removed, 3);
+        assert_eq!(stats.trimmed_removed, 1);
+        assert_eq!(stats.channels_trimmed, 1);
+
+        // Se conservan los más recientes de cada canal
+        let page = state.get_channel_messages(&busy, &history_query(None, None)).await.unwrap();
+        assert_eq!(ids(&page), busy_recent[1..]);
+        let page = state.get_channel_messages(&quiet, &history_query(None, None)).await.unwrap();
+        assert_eq!(ids(&page), quiet_recent);
+
+        // Las filas de los mensajes borrados desaparecen, no solo su entrada en el índice
+        let read_txn = state.db.begin_read().unwrap();
+        let messages = read_txn.open_table(MESSAGES_TABLE).unwrap();
+        assert!(messages.get(busy_old[0].to_string().as_str()).unwrap().is_none());
+        assert!(messages.get(busy_recent[0].to_string().as_str()).unwrap().is_none());
+        assert_eq!(messages.len().unwrap(), 4);
+    }
+
+    fn binary_message(channel_id: Uuid, seq: u64, payload: &[u8], timestamp: DateTime<Utc>) -> BroadcastMessage {
+        let mut message = text_message(channel_id, "", timestamp);
+        message.seq = seq;
+        message.content_type = Some("application/octet-stream".to_string());
+        message.payload = Some(payload.to_vec());
+        message
+    }
+
+    fn stored_payload(state: &AppState, message_id: &Uuid) -> Option<Vec<u8>> {
+        let read_txn = state.db.begin_read().unwrap();
+        let payloads = read_txn.open_table(MESSAGE_PAYLOADS_TABLE).unwrap();
+        payloads.get(message_id.to_string().as_str()).unwrap().map(|payload| payload.value().to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_binary_payload_round_trip_and_removal() {
+        let dir = TempDir::new();
+        let state = test_state(&dir).await;
+        let now = Utc::now();
+        let (kept, deleted) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let expired = binary_message(kept, 1, &[0, 1, 2], now - Duration::days(10));
+        let recent = binary_message(kept, 2, &[0xff, 0x00, 0x80], now);
+        let purged = binary_message(deleted, 1, &[7; 16], now);
+        for message in [&expired, &recent, &purged] {
+            state.save_message(message).await.unwrap();
+        }
+
+        // El contenido va en bruto en su tabla y no dentro del JSON del mensaje
+        let stored = state.messages_after_seq(&kept, 1, 10).unwrap();
+        assert_eq!(stored[0].payload.as_deref(), Some(&[0xff, 0x00, 0x80][..]));
+        let page = state.get_channel_messages(&kept, &history_query(None, None)).await.unwrap();
+        assert_eq!(page.messages[1].payload, recent.payload);
+        {
+            let read_txn = state.db.begin_read().unwrap();
+            let messages = read_txn.open_table(MESSAGES_TABLE).unwrap();
+            let row: serde_json::Value =
+                serde_json::from_str(messages.get(recent.id.to_string().as_str()).unwrap().unwrap().value()).unwrap();
+            assert!(row.get("payload").is_none_or(serde_json::Value::is_null));
+        }
+
+        // La retención borra el contenido del mensaje caducado y conserva el vigente
+        state.purge_messages(now - Duration::days(1), 10).await.unwrap();
+        assert!(stored_payload(&state, &expired.id).is_none());
+        assert_eq!(stored_payload(&state, &recent.id), recent.payload);
+
+        // Y el borrado definitivo con purga, el de todos los mensajes del canal
+        let mut channel = test_channel(ChannelStatus::Deleted, ChannelSettings::default());
+        channel.id = deleted;
+        let transition = ChannelTransition {
+            from: ChannelStatus::Active,
+            to: ChannelStatus::Deleted,
+            at: now,
+            actor: "admin".to_string(),
+            reason: None,
+        };
+        assert_eq!(state.delete_channel_record(&channel, &transition, true).await.unwrap(), 1);
+        assert!(stored_payload(&state, &purged.id).is_none());
+    }
+
+    /// Estado con solo dos mensajes en memoria por canal y un canal con `count` publicados
+    async fn published_channel(dir: &TempDir, persist_messages: bool, count: usize) -> (AppState, Channel) {
+        let mut config = test_config(dir);
+        config.websocket.replay_buffer_size = 2;
+        let state = AppState::new(&config).await.unwrap();
+
+        let settings = ChannelSettings { persist_messages, ..ChannelSettings::default() };
+        let channel = loaded_channel(&state, ChannelStatus::Active, settings).await;
+        for i in 0..count {
+            state.publish(&channel, text_message(channel.id, &format!("m{}", i), Utc::now())).await.unwrap();
+        }
+        (state, channel)
+    }
+
+    /// Reenvía desde `since_seq` y devuelve la secuencia de cada frame; la de un hueco es
+    /// su punto de reanudación
+    async fn replay(state: &AppState, channel: &Channel, since_seq: u64) -> (usize, Vec<Option<u64>>) {
+        let (sender, mut receiver) = mpsc::channel(64);
+        let mut sink = Sink::Poll(sender);
+        let replayed = state
+            .replay_missed(channel, &Uuid::new_v4(), &mut sink, FrameFormat::V1, since_seq)
+            .await
+            .unwrap();
+        drop(sink);
+
+        let mut seqs = Vec::new();
+        while let Some(frame) = receiver.recv().await {
+            seqs.push(frame.seq);
+        }
+        (replayed, seqs)
+    }
+
+    #[tokio::test]
+    async fn test_replay_from_memory_and_redb() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, true, 5).await;
+
+        // Solo 4 y 5 siguen en memoria: el resto se lee de redb
+        assert_eq!(replay(&state, &channel, 0).await, (5, vec![Some(1), Some(2), Some(3), Some(4), Some(5)]));
+        assert_eq!(replay(&state, &channel, 3).await, (2, vec![Some(4), Some(5)]));
+    }
+
+    #[tokio::test]
+    async fn test_replay_gaps_and_empty_batch() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, false, 5).await;
+
+        // Sin persistencia lo que ya no está en memoria se anuncia como hueco
+        assert_eq!(replay(&state, &channel, 0).await, (2, vec![Some(3), Some(4), Some(5)]));
+
+        // Al día: nada que reenviar
+        assert_eq!(replay(&state, &channel, 5).await, (0, vec![]));
+
+        // Un cursor por delante de la última secuencia recibe solo el hueco
+        assert_eq!(replay(&state, &channel, 9).await, (0, vec![Some(5)]));
+    }
+
+    #[tokio::test]
+    async fn test_replay_activates_connection() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, true, 1).await;
+
+        let (sender, _receiver) = mpsc::channel(8);
+        let mut live = connection(Uuid::new_v4());
+        live.replaying = true;
+        let connection_id = live.id;
+        state.connections.lock().await.insert(channel.id, live);
+
+        let mut sink = Sink::Poll(sender);
+        state.replay_missed(&channel, &connection_id, &mut sink, FrameFormat::V1, 0).await.unwrap();
+
+        let connections = state.connections.lock().await;
+        let live = &connections.channel(&channel.id).unwrap()[&connection_id];
+        assert!(!live.replaying);
+        assert_eq!(live.messages_sent, 1);
+    }
+
+    #[tokio::test]
+    async fn test_seq_survives_restart_without_persistence() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, false, 3).await;
+        drop(state);
+
+        // Solo se guardó la reserva del primer mensaje; tras reiniciar la numeración sigue al final del bloque
+        let state = test_state(&dir).await;
+        assert_eq!(state.last_seq(&channel.id).await.unwrap(), SEQ_RESERVATION_BLOCK);
+
+        let (message, _) = state.publish(&channel, text_message(channel.id, "next", Utc::now())).await.unwrap();
+        assert_eq!(message.seq, SEQ_RESERVATION_BLOCK + 1);
+    }
+
+    #[tokio::test]
+    async fn test_slow_sse_client_is_dropped() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, false, 0).await;
+
+        let (sender, mut receiver) = mpsc::channel(3);
+        let slow = Connection::new(Uuid::new_v4(), "slow".to_string(), Sink::Sse(sender));
+        state.connections.lock().await.insert(channel.id, slow);
+
+        // El fan-out no espera al cliente: al no quedar sitio lo da de baja y le envía el cierre
+        let mut delivered = Vec::new();
+        for i in 0..3 {
+            let (_, sent) = state.publish(&channel, text_message(channel.id, &format!("m{}", i), Utc::now())).await.unwrap();
+            delivered.push(sent);
+        }
+        assert_eq!(delivered, [1, 1, 0]);
+        assert_eq!(state.connections.lock().await.count(&channel.id), 0);
+
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        assert!(matches!(events.as_slice(), [SseEvent::Message { .. }, SseEvent::Message { .. }, SseEvent::Close]));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_websocket_does_not_block_other_channels() {
+        let dir = TempDir::new();
+        let (state, stalled_channel) = published_channel(&dir, false, 0).await;
+        let other_channel = loaded_channel(&state, ChannelStatus::Active, ChannelSettings::default()).await;
+
+        // Cola de un cliente WebSocket que no lee nunca; su tarea de escritura no avanza
+        let (queue, _unread) = mpsc::channel(2);
+        let writer = tokio::spawn(std::future::pending::<()>());
+        let session = SessionWriter { queue, writer: writer.abort_handle() };
+        let stalled = Connection::new(Uuid::new_v4(), "stalled".to_string(), Sink::WebSocket(session));
+        let (sender, mut receiver) = mpsc::channel(8);
+        let listener = Connection::new(Uuid::new_v4(), "listener".to_string(), Sink::Poll(sender));
+        {
+            let mut connections = state.connections.lock().await;
+            connections.insert(stalled_channel.id, stalled);
+            connections.insert(other_channel.id, listener);
+        }
+
+        let publishes = async {
+            let mut delivered = Vec::new();
+            for i in 0..4 {
+                let message = text_message(stalled_channel.id, &format!("m{}", i), Utc::now());
+                delivered.push(state.publish(&stalled_channel, message).await.unwrap().1);
+            }
+            let (_, sent) = state.publish(&other_channel, text_message(other_channel.id, "b", Utc::now())).await.unwrap();
+            (delivered, sent)
+        };
+        let (delivered, sent) = tokio::time::timeout(std::time::Duration::from_secs(1), publishes)
+            .await
+            .expect("publishing must not wait on a stalled client");
+
+        // Con la cola llena se da de baja y se corta su sesión sin esperarle
+        assert_eq!(delivered, [1, 1, 0, 0]);
+        assert_eq!(sent, 1);
+        assert_eq!(state.connections.lock().await.count(&stalled_channel.id), 0);
+        assert!(writer.await.unwrap_err().is_cancelled());
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_survives_resubscribing() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, false, 0).await;
+        let connection_id = Uuid::new_v4();
+        let key = RateLimitKey::Connection(connection_id);
+
+        state.connections.lock().await.insert(channel.id, connection(connection_id));
+        assert!(state.rate_limiter.check(key, 1).is_ok());
+
+        // Darse de baja del último canal no devuelve el cupo
+        state.remove_connection(&channel.id, &connection_id).await;
+        state.connections.lock().await.insert(channel.id, connection(connection_id));
+        assert!(state.rate_limiter.check(key, 1).is_err());
+
+        // Cerrar el socket sí lo olvida
+        state.remove_client(&connection_id).await;
+        assert!(state.rate_limiter.check(key, 1).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_forced_unsubscribe_revokes_hub_grant() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, false, 0).await;
+
+        let (sender, mut frames) = mpsc::channel(8);
+        let (revoked, mut revocations) = mpsc::unbounded_channel();
+        let mut hub = Connection::new(Uuid::new_v4(), "client".to_string(), Sink::Poll(sender));
+        hub.multiplexed = true;
+        hub.revoked = Some(revoked);
+        state.connections.lock().await.insert(channel.id, hub);
+
+        assert_eq!(state.close_channel_connections(&channel.id, "channel deleted").await, 1);
+
+        // La conexión sigue abierta: recibe la baja y la sesión retira el permiso del canal
+        let event: serde_json::Value = serde_json::from_str(&frames.try_recv().unwrap().frame).unwrap();
+        assert_eq!(event["event"], "unsubscribed");
+        assert_eq!(revocations.try_recv().unwrap(), channel.id);
+    }
+
+    #[tokio::test]
+    async fn test_find_connection_before_and_after_subscribing() {
+        let dir = TempDir::new();
+        let (state, channel) = published_channel(&dir, false, 0).await;
+        let connection_id = Uuid::new_v4();
+        let meta = ClientMeta {
+            remote_addr: Some("10.0.0.7:5123".to_string()),
+            user_agent: Some("probe/1.0".to_string()),
+        };
+
+        // Una conexión del hub se encuentra por id antes de su primer `subscribe`
+        state.register_client(connection_id, meta.clone()).await;
+        let accepted = state.find_connection(&connection_id).await.unwrap();
+        assert!(accepted.subscriptions.is_empty());
+        assert_eq!(accepted.remote_addr.as_deref(), Some("10.0.0.7:5123"));
+
+        let (sender, _receiver) = mpsc::channel(8);
+        let mut subscribed = Connection::new(connection_id, "client".to_string(), Sink::Poll(sender));
+        subscribed.meta = meta;
+        state.connections.lock().await.insert(channel.id, subscribed);
+
+        let (message, _) = state.publish(&channel, text_message(channel.id, "hello", Utc::now())).await.unwrap();
+        let expected_bytes = Frame::message(&message, &channel.name, FrameFormat::default()).unwrap().size() as u64;
+
+        let found = state.find_connection(&connection_id).await.unwrap();
+        let subscription = &found.subscriptions[0];
+        assert_eq!(subscription.channel_id, channel.id);
+        assert_eq!(subscription.user_agent.as_deref(), Some("probe/1.0"));
+        assert_eq!((subscription.messages_sent, subscription.bytes_sent), (1, expected_bytes));
+
+        // La presencia no expone la dirección ni el agente de los demás clientes
+        let presence = serde_json::to_value(state.list_presence(&channel.id).await).unwrap();
+        assert_eq!(presence[0]["id"], serde_json::json!(connection_id));
+        assert!(presence[0].get("remote_addr").is_none());
+        assert!(presence[0].get("user_agent").is_none());
+
+        state.remove_client(&connection_id).await;
+        assert!(state.find_connection(&connection_id).await.is_none());
+    }
+}
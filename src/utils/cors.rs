@@ -0,0 +1,35 @@
+use crate::config::CorsConfig;
+use actix_cors::Cors;
+
+const ANY_ORIGIN: &str = "*";
+
+/// Construye el middleware CORS a partir de `CorsConfig`. `"*"` permite cualquier origen.
+pub fn build_cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .max_age(Some(config.max_age as usize));
+
+    if allows_any_origin(config) {
+        cors = cors.allow_any_origin().send_wildcard();
+    } else {
+        for origin in &config.allowed_origins {
+            cors = cors.allowed_origin(origin);
+        }
+    }
+
+    cors
+}
+
+/// Comprueba un header `Origin` contra la lista de orígenes permitidos
+pub fn is_origin_allowed(config: &CorsConfig, origin: &str) -> bool {
+    allows_any_origin(config)
+        || config
+            .allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+}
+
+fn allows_any_origin(config: &CorsConfig) -> bool {
+    config.allowed_origins.iter().any(|origin| origin == ANY_ORIGIN)
+}
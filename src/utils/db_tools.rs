@@ -2,6 +2,7 @@ use redb::{Database, ReadableTable};
 use crate::models::channel::Channel;
 use crate::state::CHANNELS_TABLE;
 
+#[allow(dead_code)]
 pub async fn inspect_database(db_path: &str) -> anyhow::Result<()> {
     let db = Database::open(db_path)?;
     let read_txn = db.begin_read()?;
@@ -1 +1,6 @@
-pub mod db_tools;
\ No newline at end of file
+pub mod cors;
+pub mod db_tools;
+pub mod rate_limit;
+pub mod sse;
+#[cfg(test)]
+pub mod test_support;
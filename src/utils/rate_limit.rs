@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Token bucket con capacidad igual al límite por minuto y recarga continua
+#[derive(Debug)]
+pub struct TokenBucket {
+    per_minute: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(per_minute: u32, now: Instant) -> Self {
+        Self {
+            per_minute,
+            tokens: per_minute as f64,
+            last_refill: now,
+        }
+    }
+
+    fn refill_per_sec(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+
+    /// Consume un token o devuelve cuánto falta para que haya uno disponible
+    pub fn try_acquire(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec()).min(self.per_minute as f64);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec()))
+        }
+    }
+
+    /// Ajusta el límite si la configuración del canal cambió
+    fn set_limit(&mut self, per_minute: u32) {
+        if self.per_minute != per_minute {
+            self.per_minute = per_minute;
+            self.tokens = self.tokens.min(per_minute as f64);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// Publicaciones REST en un canal
+    Channel(Uuid),
+    /// Mensajes enviados por una conexión WebSocket
+    Connection(Uuid),
+}
+
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<RateLimitKey, TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Comprueba el límite `per_minute` para `key`. `0` desactiva el límite.
+    pub fn check(&self, key: RateLimitKey, per_minute: u32) -> Result<(), Duration> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let bucket = buckets
+            .entry(key)
+            .or_insert_with(|| TokenBucket::new(per_minute, now));
+
+        bucket.set_limit(per_minute);
+        bucket.try_acquire(now)
+    }
+
+    pub fn remove(&self, key: &RateLimitKey) {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_allows_burst_up_to_limit() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(3, now);
+
+        assert!(bucket.try_acquire(now).is_ok());
+        assert!(bucket.try_acquire(now).is_ok());
+        assert!(bucket.try_acquire(now).is_ok());
+
+        let retry_after = bucket.try_acquire(now).unwrap_err();
+        assert_eq!(retry_after.as_secs(), 20);
+    }
+
+    #[test]
+    fn test_bucket_refills_over_time() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(60, now);
+
+        for _ in 0..60 {
+            assert!(bucket.try_acquire(now).is_ok());
+        }
+        assert!(bucket.try_acquire(now).is_err());
+        assert!(bucket.try_acquire(now + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_zero_limit_is_unlimited() {
+        let limiter = RateLimiter::default();
+        let key = RateLimitKey::Channel(Uuid::new_v4());
+
+        for _ in 0..1000 {
+            assert!(limiter.check(key, 0).is_ok());
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use actix_web::web::Bytes;
+use futures_util::Stream;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+
+/// Frames en cola por conexión SSE; con la cola llena el cliente se da de baja
+pub const SSE_CHANNEL_CAPACITY: usize = 64;
+
+/// Evento pendiente de escribir en una respuesta `text/event-stream`
+#[derive(Debug)]
+pub enum SseEvent {
+    /// Frame JSON del canal; `id` es la secuencia del mensaje para `Last-Event-ID`
+    Message { id: Option<u64>, data: String },
+    /// Comentario que mantiene viva la conexión a través de proxies
+    Comment(&'static str),
+    /// Termina el stream
+    Close,
+}
+
+impl SseEvent {
+    pub fn encode(&self) -> Bytes {
+        match self {
+            SseEvent::Message { id: Some(id), data } => Bytes::from(format!("id: {}\ndata: {}\n\n", id, data)),
+            SseEvent::Message { id: None, data } => Bytes::from(format!("data: {}\n\n", data)),
+            SseEvent::Comment(comment) => Bytes::from(format!(": {}\n\n", comment)),
+            SseEvent::Close => Bytes::new(),
+        }
+    }
+}
+
+/// Encola un evento esperando a que haya sitio, sin ocupar el hueco reservado para
+/// `SseEvent::Close`. Solo para envíos propios de la conexión, como el reenvío inicial.
+pub async fn send_event(sender: &mpsc::Sender<SseEvent>, event: SseEvent) -> anyhow::Result<()> {
+    let mut permits = sender
+        .reserve_many(2)
+        .await
+        .map_err(|_| anyhow::anyhow!("SSE client disconnected"))?;
+    if let Some(permit) = permits.next() {
+        permit.send(event);
+    }
+    Ok(())
+}
+
+/// Encola un evento sin esperar nunca al cliente; es lo que usa el fan-out. El último hueco de la cola queda
+/// reservado para `SseEvent::Close`, así que siempre se puede cerrar a un cliente lento.
+pub fn try_send_event(sender: &mpsc::Sender<SseEvent>, event: SseEvent) -> anyhow::Result<()> {
+    if !matches!(event, SseEvent::Close) && sender.capacity() <= 1 {
+        anyhow::bail!("SSE client is too slow");
+    }
+
+    sender.try_send(event).map_err(|e| match e {
+        TrySendError::Full(_) => anyhow::anyhow!("SSE client is too slow"),
+        TrySendError::Closed(_) => anyhow::anyhow!("SSE client disconnected"),
+    })
+}
+
+/// Cuerpo de la respuesta SSE: termina con `SseEvent::Close` o cuando se sueltan todos los emisores
+pub fn event_stream(
+    receiver: mpsc::Receiver<SseEvent>,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    futures_util::stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            Some(SseEvent::Close) | None => None,
+            Some(event) => Some((Ok(event.encode()), receiver)),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_message_with_id() {
+        let event = SseEvent::Message { id: Some(7), data: "{\"a\":1}".to_string() };
+        assert_eq!(event.encode(), Bytes::from("id: 7\ndata: {\"a\":1}\n\n"));
+    }
+
+    #[test]
+    fn test_slow_client_keeps_room_for_close() {
+        let (sender, mut receiver) = mpsc::channel(3);
+        for id in 0..2 {
+            try_send_event(&sender, SseEvent::Message { id: Some(id), data: String::new() }).unwrap();
+        }
+
+        assert!(try_send_event(&sender, SseEvent::Comment("ping")).is_err());
+        try_send_event(&sender, SseEvent::Close).unwrap();
+
+        drop(receiver.try_recv());
+        drop(receiver);
+        assert!(try_send_event(&sender, SseEvent::Close).is_err());
+    }
+
+    #[test]
+    fn test_encode_comment() {
+        assert_eq!(SseEvent::Comment("ping").encode(), Bytes::from(": ping\n\n"));
+    }
+}
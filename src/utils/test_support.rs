@@ -0,0 +1,79 @@
+//! Utilidades para los tests que necesitan una base de datos real
+use crate::config::Config;
+use crate::models::channel::{Channel, ChannelSettings, ChannelStatus};
+use crate::models::message::{BroadcastMessage, MessageSender, MessageType};
+use crate::state::AppState;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Directorio temporal que se borra al salir del test
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!("emit-hub-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).expect("create temp dir");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn test_config(dir: &TempDir) -> Config {
+    Config {
+        db_path: dir.path().join("emit_hub.redb").to_string_lossy().into_owned(),
+        ..Config::default()
+    }
+}
+
+pub async fn test_state(dir: &TempDir) -> AppState {
+    AppState::new(&test_config(dir)).await.expect("open test state")
+}
+
+pub fn text_message(channel_id: Uuid, content: &str, timestamp: DateTime<Utc>) -> BroadcastMessage {
+    BroadcastMessage {
+        id: Uuid::new_v4(),
+        channel_id,
+        content: content.to_string(),
+        message_type: MessageType::Broadcast,
+        sender: MessageSender::Server,
+        timestamp,
+        seq: 0,
+        content_type: None,
+        payload: None,
+        data: None,
+        headers: BTreeMap::new(),
+    }
+}
+
+/// Canal guardado en redb y cargado en memoria, como uno creado por la API
+pub async fn loaded_channel(state: &AppState, status: ChannelStatus, settings: ChannelSettings) -> Channel {
+    let channel = test_channel(status, settings);
+    state.save_channel(&channel).await.expect("save test channel");
+    state.active_channels.write().await.insert(channel.id, channel.clone());
+    channel
+}
+
+pub fn test_channel(status: ChannelStatus, settings: ChannelSettings) -> Channel {
+    let now = Utc::now();
+    Channel {
+        id: Uuid::new_v4(),
+        name: "test".to_string(),
+        description: None,
+        status,
+        created_at: now,
+        updated_at: now,
+        settings,
+        buffered: Default::default(),
+    }
+}